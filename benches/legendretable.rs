@@ -0,0 +1,53 @@
+use std::hint::black_box;
+use std::time::Instant;
+use pulstar::*;
+
+
+// Compares the time needed to evaluate P_l^m and its first two theta-derivatives for all
+// 0 <= m <= l <= lmax in many surface points, either with LegendreTable or with repeated
+// calls to plmcos() and the pole-safe derivatives. Run with `cargo bench`.
+
+
+
+
+
+fn main() {
+
+    let n_theta = 2000;
+    let thetas: Vec<f64> = (0..n_theta).map(|j| std::f64::consts::PI * (j as f64 + 0.5) / n_theta as f64).collect();
+
+    for lmax in [4_u16, 8, 16, 32] {
+
+        let start = Instant::now();
+        let mut table = LegendreTable::new(lmax, 0.0, 1.0);
+        let mut sum = 0.0;
+        for theta in thetas.iter() {
+            table.update(theta.sin(), theta.cos());
+            for l in 0..=lmax {
+                for m in 0..=l {
+                    sum += table.plm(l, m) + table.deriv1(l, m) + table.deriv2(l, m);
+                }
+            }
+        }
+        black_box(sum);
+        let table_time = start.elapsed();
+
+        let start = Instant::now();
+        let mut sum = 0.0;
+        for theta in thetas.iter() {
+            let (sintheta, costheta) = (theta.sin(), theta.cos());
+            for l in 0..=lmax {
+                for m in 0..=l {
+                    sum += plmcos(l, m, sintheta, costheta)
+                         + deriv1_plmcos_dtheta_polesafe(l, m, sintheta, costheta)
+                         + deriv2_plmcos_dtheta_polesafe(l, m, sintheta, costheta);
+                }
+            }
+        }
+        black_box(sum);
+        let plmcos_time = start.elapsed();
+
+        println!("lmax = {:2}: LegendreTable {:10.3?}, plmcos {:10.3?}, speed-up {:6.1}",
+                 lmax, table_time, plmcos_time, plmcos_time.as_secs_f64() / table_time.as_secs_f64());
+    }
+}
@@ -4,6 +4,7 @@
 /// This code is a combination and a variant of the functions 
 /// gammln() and factrl() in Numerical Recipes in C., Press et al., 1992
 ///
+#[allow(clippy::approx_constant)]
 pub fn lnfac(n: i32) -> f64{
 
     assert!(n >= 0, "lnfac(): n < 0");
@@ -34,9 +35,9 @@ pub fn lnfac(n: i32) -> f64{
         let mut temp: f64 = x + 5.5;
         temp -= (x + 0.5) * temp.ln();
         let mut ser: f64 = 1.000000000190015;
-        for j in 0..=5 { 
+        for cof in COF.iter() { 
             y += 1.0;
-            ser += COF[j]/y;
+            ser += cof/y;
         }
 
         return -temp + (2.5066282746310005 * ser / x).ln();
@@ -93,3 +94,188 @@ pub fn binomial(n: i32, k: i32) -> f64 {
     }
 } 
 
+
+
+
+
+
+
+
+
+
+/// Compute the abscissas and weights of the n-point Gauss-Legendre quadrature on [-1, 1]
+///
+/// This is a variant of the function gauleg() in Numerical Recipes in C., Press et al., 1992.
+/// The abscissas are returned in increasing order.
+///
+pub fn gauss_legendre(n: usize) -> (Vec<f64>, Vec<f64>) {
+
+    assert!(n > 0, "gauss_legendre(): n = 0");
+
+    let mut abscissas = vec![0.0; n];
+    let mut weights = vec![0.0; n];
+
+    // The roots are symmetric, so only compute half of them
+
+    for i in 0..n.div_ceil(2) {
+
+        // Start from an approximation of the i-th root and refine it with Newton's method
+
+        let mut x = -f64::cos(std::f64::consts::PI * (i as f64 + 0.75) / (n as f64 + 0.5));
+        let mut dp: f64 = 1.0;
+        for _ in 0..100 {
+            let mut p1: f64 = 1.0;
+            let mut p2: f64 = 0.0;
+            for j in 1..=n {
+                let p3 = p2;
+                p2 = p1;
+                p1 = ((2 * j - 1) as f64 * x * p2 - (j - 1) as f64 * p3) / j as f64;
+            }
+            dp = n as f64 * (x * p1 - p2) / (x * x - 1.0);
+            let dx = p1 / dp;
+            x -= dx;
+            if dx.abs() < 1.0e-15 {
+                break;
+            }
+        }
+
+        abscissas[i] = x;
+        abscissas[n-1-i] = -x;
+        weights[i] = 2.0 / ((1.0 - x * x) * dp * dp);
+        weights[n-1-i] = weights[i];
+    }
+
+    return (abscissas, weights);
+}
+
+
+
+
+
+
+
+
+
+
+/// Solve the linear system A x = b with Gaussian elimination and partial pivoting
+///
+/// # Arguments:
+/// * `matrix` - the n x n matrix A, as a vector of rows
+/// * `rhs`    - the right-hand side b
+///
+/// Returns None if the matrix is singular to working precision, i.e. if a pivot is not larger than
+/// n epsilon times the largest element of A
+///
+pub fn solve_linear_system(mut matrix: Vec<Vec<f64>>, mut rhs: Vec<f64>) -> Option<Vec<f64>> {
+
+    let n = rhs.len();
+    assert!(matrix.len() == n && matrix.iter().all(|row| row.len() == n), "solve_linear_system(): dimensions do not match");
+
+    let scale = matrix.iter().flatten().fold(0.0_f64, |largest, element| largest.max(element.abs()));
+    let tolerance = n as f64 * f64::EPSILON * scale;
+
+    for column in 0..n {
+
+        // Swap the row with the largest pivot into place, and eliminate the column below it
+
+        let pivot = (column..n).max_by(|&i, &j| matrix[i][column].abs().total_cmp(&matrix[j][column].abs())).unwrap();
+        if matrix[pivot][column].abs() <= tolerance {
+            return None;
+        }
+        matrix.swap(column, pivot);
+        rhs.swap(column, pivot);
+
+        let (upper, lower) = matrix.split_at_mut(column + 1);
+        let pivot_row = &upper[column];
+        for (offset, row) in lower.iter_mut().enumerate() {
+            let factor = row[column] / pivot_row[column];
+            for (element, pivot_element) in row[column..].iter_mut().zip(pivot_row[column..].iter()) {
+                *element -= factor * pivot_element;
+            }
+            rhs[column + 1 + offset] -= factor * rhs[column];
+        }
+    }
+
+    let mut solution = vec![0.0; n];
+    for row in (0..n).rev() {
+        let sum: f64 = (row+1..n).map(|k| matrix[row][k] * solution[k]).sum();
+        solution[row] = (rhs[row] - sum) / matrix[row][row];
+    }
+
+    return Some(solution);
+}
+
+
+
+
+
+
+
+
+
+
+/// Fit c + \sum_j (a_j cos(2 pi f_j t) + b_j sin(2 pi f_j t)) to a time series with linear least squares
+///
+/// # Arguments:
+/// * `times`       - times of the observations [d]
+/// * `values`      - the observed values
+/// * `frequencies` - the frequencies f_j [c/d]
+///
+/// Returns the constant c and the coefficients (a_j, b_j) of each frequency, or None if the normal
+/// equations are singular, e.g. for coinciding frequencies
+///
+pub fn fit_sinusoids(times: &[f64], values: &[f64], frequencies: &[f64]) -> Option<(f64, Vec<(f64, f64)>)> {
+
+    assert!(times.len() == values.len(), "fit_sinusoids(): times and values differ in length");
+    assert!(times.len() > 2 * frequencies.len(), "fit_sinusoids(): more parameters than observations");
+
+    // Accumulate the normal equations of the basis functions 1, cos(2 pi f_j t), sin(2 pi f_j t)
+
+    let size = 2 * frequencies.len() + 1;
+    let mut matrix = vec![vec![0.0; size]; size];
+    let mut rhs = vec![0.0; size];
+    let mut basis = vec![0.0; size];
+    for (time, value) in times.iter().zip(values.iter()) {
+        basis[0] = 1.0;
+        for (j, frequency) in frequencies.iter().enumerate() {
+            let (sine, cosine) = (2.0 * std::f64::consts::PI * frequency * time).sin_cos();
+            basis[2*j + 1] = cosine;
+            basis[2*j + 2] = sine;
+        }
+        for i in 0..size {
+            rhs[i] += basis[i] * value;
+            for k in 0..size {
+                matrix[i][k] += basis[i] * basis[k];
+            }
+        }
+    }
+
+    let solution = solve_linear_system(matrix, rhs)?;
+    let coefficients = (0..frequencies.len()).map(|j| (solution[2*j + 1], solution[2*j + 2])).collect();
+
+    return Some((solution[0], coefficients));
+}
+
+
+
+
+
+
+
+
+
+
+/// Number of seconds in a day, to convert frequencies in c/d to angular frequencies in rad/s
+pub const SECONDS_PER_DAY: f64 = 86400.0;
+
+/// Nominal solar radius [km] (IAU 2015 Resolution B3)
+pub const SOLAR_RADIUS_KM: f64 = 695700.0;
+
+/// Nominal solar mass parameter G M_sun [km^3/s^2] (IAU 2015 Resolution B3)
+pub const SOLAR_GM_KM3_S2: f64 = 1.3271244e11;
+
+/// Speed of light in vacuum [km/s]
+pub const SPEED_OF_LIGHT_KM_S: f64 = 299792.458;
+
+/// Boltzmann constant divided by the atomic mass unit [km^2 s^-2 K^-1]
+pub const BOLTZMANN_OVER_AMU: f64 = 8.314462618e-3;
@@ -0,0 +1,517 @@
+use std::f64::consts::PI;
+use num_complex::Complex;
+use crate::auxilliary::*;
+use crate::intrinsicprofile::faddeeva;
+
+
+// Broadening kernels are sampled on the step of a uniform velocity grid and normalised to unit sum, so that
+// the convolution preserves the equivalent width and the continuum. Spectra on a grid that is uniform in
+// ln(lambda) are uniform in velocity with step c * d ln(lambda). For a short spectrum on a grid uniform in
+// wavelength, the velocity step c * d lambda / lambda_c at the central wavelength lambda_c is a good approximation.
+// velocity_step() converts a wavelength grid and rejects grids on which the velocity step varies too much.
+
+
+
+
+
+/// A broadening kernel K(v) sampled at v_j = j * step, -n <= j <= n, with \sum_j K(v_j) = 1
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct Kernel {
+    values: Vec<f64>,                // K(v_j) at index j + n
+    step: f64,                       // Velocity step of the grid [km/s]
+}
+
+
+
+
+
+
+
+
+
+impl Kernel {
+
+    /// Creates a kernel from user-supplied values on the velocity grid. The values are normalised to unit sum.
+    ///
+    /// # Arguments
+    ///
+    /// * `values` - the kernel at v_j = j * step, -n <= j <= n, i.e. an odd number of values centred on v = 0
+    /// * `step`   - velocity step of the grid [km/s]
+    ///
+    pub fn new(values: Vec<f64>, step: f64) -> Self {
+
+        assert!(values.len() % 2 == 1, "Kernel::new(): need an odd number of values");
+        assert!(step > 0.0, "Kernel::new(): step <= 0");
+
+        let sum: f64 = values.iter().sum();
+        assert!(sum != 0.0, "Kernel::new(): kernel with zero sum");
+
+        Kernel { values: values.iter().map(|value| value / sum).collect(), step }
+    }
+
+
+
+
+    /// Creates a kernel by sampling a user-supplied function K(v) on |v| <= half_width [km/s]
+    ///
+    pub fn from_function<F: Fn(f64) -> f64>(kernel: F, half_width: f64, step: f64) -> Self {
+        let n = (half_width / step).ceil() as i64;
+        return Kernel::new((-n..=n).map(|j| kernel(j as f64 * step)).collect(), step);
+    }
+
+
+
+
+    /// Creates a Gaussian kernel with standard deviation `sigma` [km/s], truncated at 5 sigma
+    ///
+    pub fn gaussian(sigma: f64, step: f64) -> Self {
+
+        assert!(sigma > 0.0, "Kernel::gaussian(): sigma <= 0");
+
+        // A kernel narrower than the step is sampled poorly, so integrate it over each pixel
+
+        return Kernel::from_function(|v| {
+            let lower = (v - 0.5 * step) / (2.0_f64.sqrt() * sigma);
+            let upper = (v + 0.5 * step) / (2.0_f64.sqrt() * sigma);
+            erf(upper) - erf(lower)
+        }, 5.0 * sigma + step, step);
+    }
+
+
+
+
+    /// Creates the Gaussian instrumental profile of a spectrograph with resolving power R = lambda / FWHM,
+    /// i.e. with FWHM = c / R in velocity
+    ///
+    pub fn instrumental(resolving_power: f64, step: f64) -> Self {
+        let fwhm = SPEED_OF_LIGHT_KM_S / resolving_power;
+        return Kernel::gaussian(fwhm / (8.0 * 2.0_f64.ln()).sqrt(), step);
+    }
+
+
+
+
+    /// Creates the rotation kernel of a rigidly rotating star with a linear limb darkening law (Gray 2005, Eq. 18.14)
+    ///     G(v) = (2 (1 - u) sqrt(1 - x^2) + pi u (1 - x^2) / 2) / (pi vsini (1 - u/3)),   x = v / vsini
+    ///
+    /// # Arguments
+    ///
+    /// * `vsini` - projected equatorial rotation velocity [km/s]
+    /// * `u`     - linear limb darkening coefficient
+    /// * `step`  - velocity step of the grid [km/s]
+    ///
+    pub fn rotation(vsini: f64, u: f64, step: f64) -> Self {
+
+        assert!(vsini > 0.0, "Kernel::rotation(): vsini <= 0");
+
+        // Average over each pixel, so that the sharp edges at +/- vsini are sampled well
+
+        const N_SUB: usize = 16;
+        return Kernel::from_function(|v| {
+            (0..N_SUB).map(|k| {
+                let x = (v + step * ((k as f64 + 0.5) / N_SUB as f64 - 0.5)) / vsini;
+                if x.abs() >= 1.0 { 0.0 } else { 2.0 * (1.0 - u) * (1.0 - x * x).sqrt() + 0.5 * PI * u * (1.0 - x * x) }
+            }).sum()
+        }, vsini + step, step);
+    }
+
+
+
+
+    /// Creates the radial-tangential macroturbulence kernel of Gray (2005, Eq. 17.10), without limb darkening.
+    /// A fraction A_R of the surface has Gaussian motions with dispersion zeta_R / sqrt(2) along the normal,
+    /// and the rest Gaussian motions with dispersion zeta_T / sqrt(2) in the tangential plane. Integrating over
+    /// the disk gives for both components
+    ///     M(v) = 2 / (sqrt(pi) zeta) \int_0^1 exp(-x^2 / mu^2) dmu = 2 / (sqrt(pi) zeta) (exp(-x^2) - sqrt(pi) |x| erfc(|x|))
+    /// with x = v / zeta.
+    ///
+    /// # Arguments
+    ///
+    /// * `zeta_radial`     - radial macroturbulence zeta_R [km/s]
+    /// * `zeta_tangential` - tangential macroturbulence zeta_T [km/s]
+    /// * `radial_fraction` - area fraction A_R of the radial component, usually 0.5
+    /// * `step`            - velocity step of the grid [km/s]
+    ///
+    pub fn radial_tangential(zeta_radial: f64, zeta_tangential: f64, radial_fraction: f64, step: f64) -> Self {
+
+        assert!(zeta_radial > 0.0 && zeta_tangential > 0.0, "Kernel::radial_tangential(): zeta <= 0");
+        assert!((0.0..=1.0).contains(&radial_fraction), "Kernel::radial_tangential(): radial fraction not in [0, 1]");
+
+        let component = |v: f64, zeta: f64| {
+
+            // With the Faddeeva function w(ix) = exp(x^2) erfc(x) for x >= 0
+
+            let x = v.abs() / zeta;
+            2.0 / (PI.sqrt() * zeta) * (-x * x).exp() * (1.0 - PI.sqrt() * x * faddeeva(Complex::new(0.0, x)).re)
+        };
+        let half_width = 5.0 * zeta_radial.max(zeta_tangential) + step;
+
+        return Kernel::from_function(|v| radial_fraction * component(v, zeta_radial) + (1.0 - radial_fraction) * component(v, zeta_tangential),
+                                     half_width, step);
+    }
+
+
+
+
+    /// Returns the kernel values at v_j = j * step, -n <= j <= n
+    ///
+    pub fn values(&self) -> &[f64] {
+        return &self.values;
+    }
+
+
+
+
+    /// Returns the velocity step of the grid [km/s]
+    ///
+    pub fn step(&self) -> f64 {
+        return self.step;
+    }
+
+
+
+
+    /// Returns the kernel convolved with another kernel on the same grid, e.g. to combine the instrumental
+    /// profile and the macroturbulence into a single kernel
+    ///
+    pub fn convolve(&self, other: &Kernel) -> Self {
+
+        assert!((self.step - other.step).abs() <= 1.0e-12 * self.step, "Kernel::convolve(): kernels on different grids");
+
+        let mut values = vec![0.0; self.values.len() + other.values.len() - 1];
+        for (i, a) in self.values.iter().enumerate() {
+            for (j, b) in other.values.iter().enumerate() {
+                values[i + j] += a * b;
+            }
+        }
+
+        return Kernel::new(values, self.step);
+    }
+}
+
+
+
+
+
+
+
+
+
+/// Convolves a profile on a uniform velocity grid with a kernel with the same step. Beyond the ends of the
+/// profile the first and last values are repeated, so that a continuum-normalised profile keeps its continuum.
+/// Long profiles with wide kernels are convolved with a fast Fourier transform, the others directly.
+///
+/// # Arguments
+///
+/// * `profile` - the profile, e.g. the continuum-normalised flux
+/// * `kernel`  - the broadening kernel, sampled with the step of the profile
+///
+pub fn convolve(profile: &[f64], kernel: &Kernel) -> Vec<f64> {
+
+    assert!(!profile.is_empty(), "convolve(): empty profile");
+
+    // The direct convolution costs n_profile * n_kernel operations, the FFT a few times N log2(N)
+
+    let size = (profile.len() + kernel.values.len()).next_power_of_two();
+    if profile.len() * kernel.values.len() > 8 * size * size.trailing_zeros() as usize {
+        return convolve_fft(profile, kernel);
+    } else {
+        return convolve_direct(profile, kernel);
+    }
+}
+
+
+
+
+
+
+
+
+
+/// Returns the mean velocity step [km/s] of a wavelength grid, c ln(lambda_last / lambda_first) / (n - 1).
+/// Panics if the velocity step c (lambda_{i+1} - lambda_i) / lambda_{i+1/2} of any pixel deviates from the
+/// mean by more than a fraction `tolerance`, e.g. 1.0e-3, so that the broadening of a kernel with half width
+/// w is misplaced by at most tolerance * w anywhere in the profile.
+///
+/// # Arguments
+///
+/// * `wavelengths` - the increasing wavelengths of the grid, in any unit
+/// * `tolerance`   - the allowed relative deviation of the velocity step
+///
+pub fn velocity_step(wavelengths: &[f64], tolerance: f64) -> f64 {
+
+    assert!(wavelengths.len() >= 2, "velocity_step(): need at least two wavelengths");
+    assert!(wavelengths[0] > 0.0 && wavelengths.windows(2).all(|pair| pair[1] > pair[0]),
+            "velocity_step(): wavelengths are not positive and increasing");
+
+    let mean = SPEED_OF_LIGHT_KM_S * (wavelengths[wavelengths.len() - 1] / wavelengths[0]).ln() / (wavelengths.len() - 1) as f64;
+    for pair in wavelengths.windows(2) {
+        let step = SPEED_OF_LIGHT_KM_S * 2.0 * (pair[1] - pair[0]) / (pair[1] + pair[0]);
+        assert!((step - mean).abs() <= tolerance * mean,
+                "velocity_step(): step {} km/s at {} deviates from the mean {} km/s, the grid is not uniform in velocity", step, pair[0], mean);
+    }
+
+    return mean;
+}
+
+
+
+
+
+
+
+
+
+
+/// Convolves a profile on a wavelength grid with a kernel, e.g. one created with the step of velocity_step().
+/// Panics if the grid is not uniform in velocity to within a fraction `tolerance`, or if the step of the kernel
+/// differs from the velocity step of the grid by more than that fraction.
+///
+/// # Arguments
+///
+/// * `wavelengths` - the increasing wavelengths of the profile, in any unit
+/// * `profile`     - the profile, e.g. the continuum-normalised flux
+/// * `kernel`      - the broadening kernel
+/// * `tolerance`   - the allowed relative deviation of the velocity step, see velocity_step()
+///
+pub fn convolve_wavelength(wavelengths: &[f64], profile: &[f64], kernel: &Kernel, tolerance: f64) -> Vec<f64> {
+
+    assert!(wavelengths.len() == profile.len(), "convolve_wavelength(): wavelengths and profile differ in length");
+
+    let step = velocity_step(wavelengths, tolerance);
+    assert!((kernel.step - step).abs() <= tolerance * step,
+            "convolve_wavelength(): kernel step {} km/s differs from the velocity step {} km/s of the grid", kernel.step, step);
+
+    return convolve(profile, kernel);
+}
+
+
+
+
+
+
+
+
+
+
+/// Convolves a profile directly with a kernel, see convolve()
+///
+fn convolve_direct(profile: &[f64], kernel: &Kernel) -> Vec<f64> {
+
+    let half = (kernel.values.len() / 2) as i64;
+    let last = profile.len() as i64 - 1;
+
+    return (0..profile.len() as i64).map(|i| {
+        kernel.values.iter().enumerate()
+              .map(|(k, value)| value * profile[(i + half - k as i64).clamp(0, last) as usize])
+              .sum()
+    }).collect();
+}
+
+
+
+
+
+
+
+
+
+/// Convolves a profile with a kernel by multiplying their discrete Fourier transforms, see convolve()
+///
+fn convolve_fft(profile: &[f64], kernel: &Kernel) -> Vec<f64> {
+
+    // Pad the profile with its end values on both sides, such that the circular convolution
+    // does not wrap around into the profile
+
+    let half = kernel.values.len() / 2;
+    let size = (profile.len() + 2 * half).next_power_of_two();
+    let mut signal = vec![Complex::new(profile[profile.len() - 1], 0.0); size];
+    for (j, value) in signal.iter_mut().enumerate().take(profile.len() + half) {
+        *value = Complex::new(if j < half { profile[0] } else { profile[j - half] }, 0.0);
+    }
+
+    // The kernel with its centre at index 0, and negative velocities wrapped to the end
+
+    let mut response = vec![Complex::new(0.0, 0.0); size];
+    for (k, value) in kernel.values.iter().enumerate() {
+        response[(k + size - half) % size] = Complex::new(*value, 0.0);
+    }
+
+    fft(&mut signal, false);
+    fft(&mut response, false);
+    for (s, r) in signal.iter_mut().zip(response.iter()) {
+        *s *= r;
+    }
+    fft(&mut signal, true);
+
+    return signal[half..half + profile.len()].iter().map(|value| value.re / size as f64).collect();
+}
+
+
+
+
+
+
+
+
+
+/// In-place radix-2 fast Fourier transform of a sequence whose length is a power of 2 (Cooley & Tukey 1965).
+/// The inverse transform is not divided by the length.
+///
+fn fft(data: &mut [Complex<f64>], inverse: bool) {
+
+    let n = data.len();
+    assert!(n.is_power_of_two(), "fft(): length is not a power of 2");
+
+    // Bit-reversal permutation
+
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            data.swap(i, j);
+        }
+    }
+
+    // Butterflies of increasing length
+
+    let sign = if inverse { 1.0 } else { -1.0 };
+    let mut length = 2;
+    while length <= n {
+        let twiddle = Complex::from_polar(1.0, sign * 2.0 * PI / length as f64);
+        for start in (0..n).step_by(length) {
+            let mut factor = Complex::new(1.0, 0.0);
+            for k in 0..length / 2 {
+                let even = data[start + k];
+                let odd = data[start + k + length / 2] * factor;
+                data[start + k] = even + odd;
+                data[start + k + length / 2] = even - odd;
+                factor *= twiddle;
+            }
+        }
+        length <<= 1;
+    }
+}
+
+
+
+
+
+
+
+
+
+/// Computes the error function erf(x) = 1 - erfc(x), with erfc(|x|) = exp(-x^2) w(i|x|)
+///
+fn erf(x: f64) -> f64 {
+    let erfc = (-x * x).exp() * faddeeva(Complex::new(0.0, x.abs())).re;
+    return (1.0 - erfc).copysign(x);
+}
+
+
+
+
+
+
+
+
+
+
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn test_gaussian_convolution() {
+
+        // A Gaussian line convolved with the instrumental profile is a Gaussian with the widths added in quadrature
+
+        let step = 0.25;
+        let velocities: Vec<f64> = (-400..=400).map(|j| j as f64 * step).collect();
+        let line = |v: f64, sigma: f64| 1.0 - 2.0 / sigma * (-0.5 * v * v / (sigma * sigma)).exp();
+        let profile: Vec<f64> = velocities.iter().map(|v| line(*v, 5.0)).collect();
+
+        let kernel = Kernel::instrumental(60000.0, step);
+        let sigma = SPEED_OF_LIGHT_KM_S / 60000.0 / (8.0 * 2.0_f64.ln()).sqrt();
+        assert_approx_eq!(kernel.values().iter().sum::<f64>(), 1.0, 1.0e-14);
+
+        let direct = convolve_direct(&profile, &kernel);
+        let fast = convolve_fft(&profile, &kernel);
+        let automatic = convolve(&profile, &kernel);
+        let width = (25.0 + sigma * sigma).sqrt();
+        for (j, v) in velocities.iter().enumerate() {
+            assert_approx_eq!(direct[j], fast[j], 1.0e-12);
+            assert_approx_eq!(direct[j], automatic[j], 1.0e-12);
+            assert_approx_eq!(direct[j], line(*v, width), 2.0e-4);
+        }
+        assert_approx_eq!(direct[0], 1.0, 1.0e-12);
+    }
+
+    #[test]
+    fn test_rotation_and_macroturbulence_kernels() {
+
+        // Without limb darkening the rotation kernel is a semi-ellipse, with second moment vsini^2 / 4. With
+        // linear limb darkening the second moment is vsini^2 ((1 - u) / 4 + 2 u / 15) / (1 - u / 3).
+
+        let step = 0.1;
+        let vsini: f64 = 30.0;
+        for u in [0.0, 0.6, 1.0] {
+            let rotation = Kernel::rotation(vsini, u, step);
+            let n = rotation.values().len() / 2;
+            let second_moment: f64 = rotation.values().iter().enumerate().map(|(j, value)| value * ((j as f64 - n as f64) * step).powi(2)).sum();
+            assert_approx_eq!(second_moment, vsini.powi(2) * ((1.0 - u) / 4.0 + 2.0 * u / 15.0) / (1.0 - u / 3.0), 0.05);
+            let centre = (2.0 * (1.0 - u) + 0.5 * PI * u) / (PI * vsini * (1.0 - u / 3.0)) * step;
+            assert_approx_eq!(rotation.values()[n], centre, 1.0e-4 * centre);
+        }
+
+        // The radial-tangential kernel equals the integral over mu of its definition
+
+        let zeta = 6.0;
+        let macroturbulence = Kernel::radial_tangential(zeta, zeta, 0.5, step);
+        let n = macroturbulence.values().len() / 2;
+        let (abscissas, weights) = gauss_legendre(200);
+        for v in [0.0, 2.0, 7.5, 15.0] {
+            let integral: f64 = abscissas.iter().zip(weights.iter())
+                                         .map(|(x, w)| { let mu = 0.5 * (x + 1.0); 0.5 * w * (-(v / (zeta * mu)).powi(2)).exp() })
+                                         .sum();
+            let expected = 2.0 / (PI.sqrt() * zeta) * integral * step;
+            assert_approx_eq!(macroturbulence.values()[n + (v / step).round() as usize], expected, 1.0e-4 * expected.max(1.0e-3));
+        }
+    }
+
+    #[test]
+    fn test_wavelength_grid() {
+
+        // A grid uniform in ln(lambda) has a constant velocity step, and a short grid uniform in lambda nearly so
+
+        let logarithmic: Vec<f64> = (0..2001).map(|j| 5000.0 * (j as f64 * 1.0e-6).exp()).collect();
+        let step = velocity_step(&logarithmic, 1.0e-8);
+        assert_approx_eq!(step, SPEED_OF_LIGHT_KM_S * 1.0e-6, 1.0e-12);
+
+        let linear: Vec<f64> = (0..101).map(|j| 4997.5 + j as f64 * 0.05).collect();
+        let step = velocity_step(&linear, 1.0e-3);
+        assert_approx_eq!(step, SPEED_OF_LIGHT_KM_S * 0.05 / 5000.0, 1.0e-3 * step);
+
+        let profile: Vec<f64> = linear.iter().map(|lambda| 1.0 - 0.5 * (-(lambda - 5000.0).powi(2)).exp()).collect();
+        let kernel = Kernel::rotation(20.0, 0.6, step);
+        assert_eq!(convolve_wavelength(&linear, &profile, &kernel, 1.0e-3), convolve(&profile, &kernel));
+    }
+
+    #[test]
+    #[should_panic(expected = "not uniform in velocity")]
+    fn test_wavelength_grid_panic() {
+        let linear: Vec<f64> = (0..2001).map(|j| 4000.0 + j as f64 * 1.0).collect();
+        velocity_step(&linear, 1.0e-3);
+    }
+}
@@ -1,8 +1,10 @@
+use std::f64::consts::PI;
 use crate::sphericalharmonics::*;
+use crate::auxilliary::*;
 
 
 /// Compute the Lagrangian displacement vector in spherical coordinates
-/// 
+///
 /// # Arguments:
 /// # `phase` - omega*t + psi         [rad]
 /// * `theta` - colatitude coordinate [rad]
@@ -12,17 +14,25 @@ use crate::sphericalharmonics::*;
 /// * `ampl_radial`     - amplitude in the radial direction * Y_l^m
 /// * `ampl_tangential` - amplitude in the tangential direction * Y_l^m
 ///
+/// For both signs of m the latitudinal dependence is P_l^|m|(cos(theta)) and its derivative,
+/// so that the modes m and -m only differ in the sign of m in cos(phase + m*phi).
+///
+/// The phi-component is the perturbation of the azimuth, i.e. the physical component divided by sin(theta).
+/// At the poles the result is the limit along the meridian phi, except for |m| = 1: the horizontal displacement
+/// of those modes does not vanish at the poles, so that delta phi diverges as 1/sin(theta), and exactly at the
+/// poles 0 is returned. Use displacement_polesafe() for the physical components.
+///
 pub fn displacement(phase: f64, theta: f64, phi: f64, l: u16, m: i16, ampl_radial: f64, ampl_tangential: f64) -> (f64, f64, f64) {
 
     let sintheta = theta.sin();
     let costheta = theta.cos();
-    let plmcostheta = plmcos(l, m.abs() as u16, sintheta, costheta); 
-    let dplmcostheta_dtheta = (- f64::from(l+1) * costheta * plmcostheta            // First derivative
-                               + f64::from((l as i16) - m + 1) * plmcos(l+1, m.abs() as u16, sintheta, costheta))  / sintheta;
+    let plmcostheta = plmcos(l, m.unsigned_abs(), sintheta, costheta);
+    let dplmcostheta_dtheta = deriv1_plmcos_dtheta_polesafe(l, m.unsigned_abs(), sintheta, costheta);
+    let plm_over_sin2theta = plmcos_over_sin2theta_polesafe(l, m.unsigned_abs(), sintheta, costheta);
 
     let delta_r     = ampl_radial * plmcostheta * f64::cos(phase + f64::from(m)*phi);
     let delta_theta = ampl_tangential * dplmcostheta_dtheta * f64::cos(phase + f64::from(m)*phi);
-    let delta_phi   = ampl_tangential * f64::from(-m) * plmcostheta * f64::sin(phase + f64::from(m)*phi) / (sintheta * sintheta);
+    let delta_phi   = ampl_tangential * f64::from(-m) * plm_over_sin2theta * f64::sin(phase + f64::from(m)*phi);
 
     return (delta_r, delta_theta, delta_phi)
 }
@@ -30,3 +40,765 @@ pub fn displacement(phase: f64, theta: f64, phi: f64, l: u16, m: i16, ampl_radia
 
 
 
+
+
+
+
+
+/// Returns true at the poles, where sin(theta) vanishes up to round-off, as for theta = std::f64::consts::PI
+///
+fn at_pole(sintheta: f64) -> bool {
+    return sintheta.abs() <= f64::EPSILON;
+}
+
+
+
+
+
+
+
+
+
+/// Computes P_l^m(cos(theta)) / sin^2(theta) with its limit at the poles for m = 0 and m >= 2.
+/// For m = 1 the quotient diverges at the poles, where 0 is returned.
+///
+fn plmcos_over_sin2theta_polesafe(l: u16, m: u16, sintheta: f64, costheta: f64) -> f64 {
+    return match m {
+        0 => if at_pole(sintheta) { 0.0 } else { plmcos(l, 0, sintheta, costheta) / (sintheta * sintheta) },
+        1 => if at_pole(sintheta) { 0.0 } else { plmcos_over_sintheta(l, 1, sintheta, costheta) / sintheta },
+        _ => plmcos_over_sin2theta(l, m, sintheta, costheta),
+    };
+}
+
+
+
+
+
+
+
+
+
+/// Computes dP_l^m(cos(theta))/dtheta / sin(theta) with the ladder relations of deriv1_plmcos_dtheta_polesafe(),
+/// so that its limit at the poles is obtained for m = 0 and m >= 2. For m = 1 the quotient diverges at the poles,
+/// where 0 is returned.
+///
+fn deriv1_plmcos_dtheta_over_sintheta_polesafe(l: u16, m: u16, sintheta: f64, costheta: f64) -> f64 {
+
+    if m == 0 {
+        return if l == 0 { 0.0 } else { -plmcos_over_sintheta(l, 1, sintheta, costheta) };
+    }
+
+    if m == 1 {
+        return if at_pole(sintheta) { 0.0 } else { deriv1_plmcos_dtheta_polesafe(l, 1, sintheta, costheta) / sintheta };
+    }
+
+    let ladder = f64::from(l+m) * f64::from(l-m+1);
+    let next = if m < l { plmcos_over_sintheta(l, m+1, sintheta, costheta) } else { 0.0 };
+    return 0.5 * (ladder * plmcos_over_sintheta(l, m-1, sintheta, costheta) - next);
+}
+
+
+
+
+
+
+
+
+
+/// Compute the physical components of the Lagrangian displacement vector in spherical coordinates,
+/// i.e. unlike displacement() the phi-component includes the factor sin(theta). The derivative and
+/// P_l^m / sin(theta) are computed with pole-safe recurrences, so that the result remains finite at
+/// theta = 0 and theta = pi, where it equals the limit along the meridian phi.
+///
+/// # Arguments:
+/// * `phase` - omega*t + psi         [rad]
+/// * `theta` - colatitude coordinate [rad]
+/// * `phi`   - azimuthal coordinate  [rad]
+/// * `l`     - degree of the spherical harmonic >= 0
+/// * `m`     - azimuthal number of the spherical harmonic, -l <= m <= l
+/// * `ampl_radial`     - amplitude in the radial direction * Y_l^m
+/// * `ampl_tangential` - amplitude in the tangential direction * Y_l^m
+///
+pub fn displacement_polesafe(phase: f64, theta: f64, phi: f64, l: u16, m: i16, ampl_radial: f64, ampl_tangential: f64) -> (f64, f64, f64) {
+
+    let sintheta = theta.sin();
+    let costheta = theta.cos();
+    let abs_m = m.unsigned_abs();
+    let plmcostheta = plmcos(l, abs_m, sintheta, costheta);
+    let dplmcostheta_dtheta = deriv1_plmcos_dtheta_polesafe(l, abs_m, sintheta, costheta);
+
+    // For m = 0 there is no phi-component, so that P_l^0 / sin(theta) is never needed
+
+    let plm_over_sintheta = if m == 0 { 0.0 } else { plmcos_over_sintheta(l, abs_m, sintheta, costheta) };
+
+    let xi_r     = ampl_radial * plmcostheta * f64::cos(phase + f64::from(m)*phi);
+    let xi_theta = ampl_tangential * dplmcostheta_dtheta * f64::cos(phase + f64::from(m)*phi);
+    let xi_phi   = ampl_tangential * f64::from(-m) * plm_over_sintheta * f64::sin(phase + f64::from(m)*phi);
+
+    return (xi_r, xi_theta, xi_phi)
+}
+
+
+
+
+
+
+
+
+
+/// Compute the unit normal of the displaced surface element to first order in the displacement.
+/// The element that was at (theta, phi) moves tangentially over xi_h, which turns its radial direction
+/// by xi_h / R, and the radial displacement tilts the surface over -grad_h xi_r, so that
+///     n = e_r + (xi_h - grad_h xi_r) / R
+/// The displacement may be the sum of several modes. The result (n_r, n_theta, n_phi) contains the physical
+/// components, and its projection on the line of sight, the perturbed mu, follows from line_of_sight_component().
+///
+/// # Arguments:
+/// * `displacement` - the physical components (xi_r, xi_theta, xi_phi) [R], e.g. of displacement_polesafe(),
+///   or of displacement() with delta phi multiplied by sin(theta)
+/// * `dxi_r_dtheta` - d xi_r / d theta [R], e.g. with deriv1_plmcos_dtheta()
+/// * `dxi_r_dphi_over_sintheta` - d xi_r / d phi / sin(theta) [R], e.g. with plmcos_over_sintheta() so that it is finite at the poles
+///
+pub fn perturbed_normal(displacement: (f64, f64, f64), dxi_r_dtheta: f64, dxi_r_dphi_over_sintheta: f64) -> (f64, f64, f64) {
+
+    let (_, xi_theta, xi_phi) = displacement;
+
+    return (1.0, xi_theta - dxi_r_dtheta, xi_phi - dxi_r_dphi_over_sintheta);
+}
+
+
+
+
+
+
+
+
+
+/// Compute the relative change dS/S of the area of a surface element to first order in the displacement,
+///     dS/S = 2 xi_r / R + div_h xi_h / R,   div_h xi_h = d xi_theta/dtheta + cot(theta) xi_theta + d xi_phi/dphi / sin(theta)
+/// with the physical components xi_theta and xi_phi. The displacement may be the sum of several modes.
+///
+/// # Arguments:
+/// * `xi_r`       - the radial displacement [R]
+/// * `divergence` - the horizontal divergence div_h xi_h of the tangential displacement [R], e.g. with
+///   deriv1_plmcos_dtheta() and deriv2_plmcos_dtheta() as in relative_area_change_mode()
+///
+pub fn relative_area_change(xi_r: f64, divergence: f64) -> f64 {
+    return 2.0 * xi_r + divergence;
+}
+
+
+
+
+
+
+
+
+
+/// Compute the perturbed_normal() of a single mode. Both xi_h and grad_h xi_r are gradients of the function
+/// P_l^|m|(cos(theta)) cos(phase + m phi), so that grad_h xi_r follows from displacement_polesafe() with the
+/// tangential amplitude ampl_radial. The result is finite at the poles.
+///
+/// # Arguments:
+/// * `phase` - omega*t + psi         [rad]
+/// * `theta` - colatitude coordinate [rad]
+/// * `phi`   - azimuthal coordinate  [rad]
+/// * `l`     - degree of the spherical harmonic >= 0
+/// * `m`     - azimuthal number of the spherical harmonic, -l <= m <= l
+/// * `ampl_radial`     - amplitude in the radial direction * Y_l^m [R]
+/// * `ampl_tangential` - amplitude in the tangential direction * Y_l^m [R]
+///
+pub fn perturbed_normal_mode(phase: f64, theta: f64, phi: f64, l: u16, m: i16, ampl_radial: f64, ampl_tangential: f64) -> (f64, f64, f64) {
+
+    let displacement = displacement_polesafe(phase, theta, phi, l, m, ampl_radial, ampl_tangential);
+    let (_, dxi_r_dtheta, dxi_r_dphi_over_sintheta) = displacement_polesafe(phase, theta, phi, l, m, 0.0, ampl_radial);
+
+    return perturbed_normal(displacement, dxi_r_dtheta, dxi_r_dphi_over_sintheta);
+}
+
+
+
+
+
+
+
+
+
+/// Compute the relative_area_change() of a single mode, with the horizontal divergence
+///     div_h xi_h = ampl_tangential (d^2P/dtheta^2 + cos(theta) dP/dtheta / sin(theta) - m^2 P / sin^2(theta)) cos(phase + m phi)
+/// of P = P_l^|m|(cos(theta)), which equals -l(l+1) ampl_tangential P cos(phase + m phi) by Legendre's equation.
+/// The pole-safe recurrences are used, so that the result is finite at the poles.
+///
+/// # Arguments:
+/// * `phase` - omega*t + psi         [rad]
+/// * `theta` - colatitude coordinate [rad]
+/// * `phi`   - azimuthal coordinate  [rad]
+/// * `l`     - degree of the spherical harmonic >= 0
+/// * `m`     - azimuthal number of the spherical harmonic, -l <= m <= l
+/// * `ampl_radial`     - amplitude in the radial direction * Y_l^m [R]
+/// * `ampl_tangential` - amplitude in the tangential direction * Y_l^m [R]
+///
+pub fn relative_area_change_mode(phase: f64, theta: f64, phi: f64, l: u16, m: i16, ampl_radial: f64, ampl_tangential: f64) -> f64 {
+
+    let sintheta = theta.sin();
+    let costheta = theta.cos();
+    let abs_m = m.unsigned_abs();
+
+    // For |m| = 1 the last two terms diverge separately at the poles, but their sum vanishes there,
+    // as do the values 0 that the helpers return exactly at the poles
+
+    let laplacian = deriv2_plmcos_dtheta_polesafe(l, abs_m, sintheta, costheta)
+                    + costheta * deriv1_plmcos_dtheta_over_sintheta_polesafe(l, abs_m, sintheta, costheta)
+                    - f64::from(m) * f64::from(m) * plmcos_over_sin2theta_polesafe(l, abs_m, sintheta, costheta);
+    let (xi_r, _, _) = displacement_polesafe(phase, theta, phi, l, m, ampl_radial, 0.0);
+
+    return relative_area_change(xi_r, ampl_tangential * laplacian * f64::cos(phase + f64::from(m)*phi));
+}
+
+
+
+
+
+
+
+
+
+/// Compute the relative perturbation of the local effective temperature
+///     delta T / T = f_T * ampl_radial * P_l^|m|(cos(theta)) * cos(phase + psi_T + m phi)
+/// which has the same angular dependence as the radial displacement, but its own amplitude and phase lag.
+///
+/// # Arguments:
+/// * `phase` - omega*t + psi         [rad]
+/// * `theta` - colatitude coordinate [rad]
+/// * `phi`   - azimuthal coordinate  [rad]
+/// * `l`     - degree of the spherical harmonic >= 0
+/// * `m`     - azimuthal number of the spherical harmonic, -l <= m <= l
+/// * `ampl_radial` - amplitude in the radial direction * Y_l^m [R]
+/// * `amplitude`   - f_T, the temperature amplitude relative to `ampl_radial`
+/// * `phase_lag`   - psi_T, the phase lag w.r.t. the radial displacement [rad]
+///
+#[allow(clippy::too_many_arguments)]
+pub fn temperature_perturbation(phase: f64, theta: f64, phi: f64, l: u16, m: i16, ampl_radial: f64, amplitude: f64, phase_lag: f64) -> f64 {
+    return scalar_perturbation(phase, theta, phi, l, m, ampl_radial, amplitude, phase_lag);
+}
+
+
+
+
+
+
+
+
+
+/// Compute a relative scalar perturbation of the surface, e.g. of the temperature or the gravity,
+/// with the angular dependence of the radial displacement but its own amplitude and phase lag:
+///     f * ampl_radial * P_l^|m|(cos(theta)) * cos(phase + psi_f + m phi)
+///
+/// # Arguments:
+/// * `phase` - omega*t + psi         [rad]
+/// * `theta` - colatitude coordinate [rad]
+/// * `phi`   - azimuthal coordinate  [rad]
+/// * `l`     - degree of the spherical harmonic >= 0
+/// * `m`     - azimuthal number of the spherical harmonic, -l <= m <= l
+/// * `ampl_radial` - amplitude in the radial direction * Y_l^m [R]
+/// * `amplitude`   - f, the amplitude relative to `ampl_radial`
+/// * `phase_lag`   - psi_f, the phase lag w.r.t. the radial displacement [rad]
+///
+#[allow(clippy::too_many_arguments)]
+pub fn scalar_perturbation(phase: f64, theta: f64, phi: f64, l: u16, m: i16, ampl_radial: f64, amplitude: f64, phase_lag: f64) -> f64 {
+
+    let plmcostheta = plmcos(l, m.unsigned_abs(), theta.sin(), theta.cos());
+
+    return amplitude * ampl_radial * plmcostheta * f64::cos(phase + phase_lag + f64::from(m)*phi);
+}
+
+
+
+
+
+
+
+
+
+/// Compute the relative perturbation of the local effective gravity of an adiabatic mode from its
+/// radial displacement (e.g. Cugier & Daszynska 2001, A&A 377, 113)
+///     delta g / g = -(2 + omega^2 R^3 / (G M)) delta r / R
+/// where the first term is the change of the gravitational acceleration and the second one the
+/// acceleration of the surface layers.
+///
+/// # Arguments:
+/// * `phase` - omega*t + psi         [rad]
+/// * `theta` - colatitude coordinate [rad]
+/// * `phi`   - azimuthal coordinate  [rad]
+/// * `l`     - degree of the spherical harmonic >= 0
+/// * `m`     - azimuthal number of the spherical harmonic, -l <= m <= l
+/// * `ampl_radial`  - amplitude in the radial direction * Y_l^m [R]
+/// * `acceleration` - omega^2 R^3 / (G M), i.e. 1/K for the adiabatic ratio K of Mode::adiabatic_k()
+///
+#[allow(clippy::too_many_arguments)]
+pub fn gravity_perturbation(phase: f64, theta: f64, phi: f64, l: u16, m: i16, ampl_radial: f64, acceleration: f64) -> f64 {
+
+    let (delta_r, _, _) = displacement(phase, theta, phi, l, m, ampl_radial, 0.0);
+
+    return -(2.0 + acceleration) * delta_r;
+}
+
+
+
+
+
+
+
+
+
+/// Compute the pulsation velocity vector, i.e. the time derivative of the Lagrangian displacement
+///
+/// Since the displacement varies as cos(omega*t + psi + m*phi), its time derivative equals omega
+/// times the displacement a quarter of a cycle later. Unlike displacement(), which gives the perturbation
+/// of the azimuth, the returned phi-component is the physical velocity, i.e. it includes the factor sin(theta).
+/// The result is finite at the poles (see displacement_polesafe()).
+///
+/// # Arguments:
+/// * `phase` - omega*t + psi         [rad]
+/// * `theta` - colatitude coordinate [rad]
+/// * `phi`   - azimuthal coordinate  [rad]
+/// * `l`     - degree of the spherical harmonic >= 0
+/// * `m`     - azimuthal number of the spherical harmonic, -l <= m <= l
+/// * `ampl_radial`     - amplitude in the radial direction * Y_l^m [km]
+/// * `ampl_tangential` - amplitude in the tangential direction * Y_l^m [km]
+/// * `omega` - angular pulsation frequency [rad/s]
+///
+/// The result (v_r, v_theta, v_phi) is in [km/s].
+///
+#[allow(clippy::too_many_arguments)]
+pub fn velocity(phase: f64, theta: f64, phi: f64, l: u16, m: i16, ampl_radial: f64, ampl_tangential: f64, omega: f64) -> (f64, f64, f64) {
+
+    let (xi_r, xi_theta, xi_phi) = displacement_polesafe(phase + 0.5 * PI, theta, phi, l, m, ampl_radial, ampl_tangential);
+
+    return (omega * xi_r, omega * xi_theta, omega * xi_phi)
+}
+
+
+
+
+
+
+
+
+
+/// Compute the pulsation velocity vector for a mode with a frequency in cycles per day and
+/// amplitudes expressed in units of the stellar radius.
+///
+/// # Arguments:
+/// * `phase` - omega*t + psi         [rad]
+/// * `theta` - colatitude coordinate [rad]
+/// * `phi`   - azimuthal coordinate  [rad]
+/// * `l`     - degree of the spherical harmonic >= 0
+/// * `m`     - azimuthal number of the spherical harmonic, -l <= m <= l
+/// * `ampl_radial`     - amplitude in the radial direction * Y_l^m [R]
+/// * `ampl_tangential` - amplitude in the tangential direction * Y_l^m [R]
+/// * `frequency` - pulsation frequency [c/d]
+/// * `radius`    - stellar radius [R_sun]
+///
+/// The result (v_r, v_theta, v_phi) is in [km/s].
+///
+#[allow(clippy::too_many_arguments)]
+pub fn velocity_cd(phase: f64, theta: f64, phi: f64, l: u16, m: i16, ampl_radial: f64, ampl_tangential: f64, frequency: f64, radius: f64) -> (f64, f64, f64) {
+
+    let omega = 2.0 * PI * frequency / SECONDS_PER_DAY;
+    let radius_km = radius * SOLAR_RADIUS_KM;
+
+    return velocity(phase, theta, phi, l, m, ampl_radial * radius_km, ampl_tangential * radius_km, omega)
+}
+
+
+
+
+
+
+
+
+
+
+
+
+
+
+/// Compute the tangential amplitude of a mode in a slowly rotating star, corrected for the Coriolis force
+/// to first order in the spin parameter Omega/omega. Projecting the horizontal equation of motion
+///     -omega^2 xi_h + 2 i omega (Omega x xi)_h = -grad_h Psi
+/// on grad_h Y_l^m gives the ratio K' = K_psi + 2 m Omega/omega (1 + K) / (l(l+1)), where K_psi = Psi / (omega^2 R)
+/// contains the co-rotating frequency omega = omega_0 + m C_nl Omega with the Ledoux shift (see ledoux_frequency()),
+/// i.e. K_psi = K (1 - 2 m C_nl Omega/omega). Together
+///     K' = K + 2 m Omega/omega ((1 + K) / (l(l+1)) - C_nl K)
+/// For high-order g modes C_nl -> 1/(l(l+1)) and K >> 1, so that K' - K -> 2 m Omega/omega / (l(l+1)).
+///
+/// # Arguments:
+/// * `l`     - degree of the spherical harmonic >= 0
+/// * `m`     - azimuthal number of the spherical harmonic, -l <= m <= l
+/// * `ampl_radial`     - amplitude in the radial direction * Y_l^m
+/// * `ampl_tangential` - amplitude K * ampl_radial in the tangential direction * Y_l^m in the absence of rotation
+/// * `spin`   - Omega/omega, with omega the angular pulsation frequency in the co-rotating frame
+/// * `ledoux` - Ledoux constant C_nl
+///
+pub fn coriolis_tangential_amplitude(l: u16, m: i16, ampl_radial: f64, ampl_tangential: f64, spin: f64, ledoux: f64) -> f64 {
+
+    // A radial mode has no tangential displacement
+
+    if l == 0 {
+        return ampl_tangential;
+    }
+
+    let lf = f64::from(l);
+    let correction = (ampl_radial + ampl_tangential) / (lf * (lf + 1.0)) - ledoux * ampl_tangential;
+
+    return ampl_tangential + 2.0 * f64::from(m) * spin * correction;
+}
+
+
+
+
+
+
+
+
+
+/// Compute the Lagrangian displacement vector of a mode in a slowly rotating star, including the
+/// Coriolis correction of the spheroidal part and the toroidal terms caused by the Coriolis force,
+/// to first order in the spin parameter Omega/omega (Martens & Smeyers 1982; Schrijvers et al. 1997, A&AS 121, 343):
+///     xi = Y_l^m e_r + K' grad_h Y_l^m + \sum_{l'=l\pm1} tau_{l'} e_r x grad_h Y_{l'}^m
+/// with K' of coriolis_tangential_amplitude() and, for the unnormalised P_l^m of displacement(),
+///     tau_{l+1} = -2i Omega/omega (l - |m| + 1) (1 - l K) / ((l + 1) (2l + 1))
+///     tau_{l-1} = +2i Omega/omega (l + |m|) (1 + (l + 1) K) / (l (2l + 1))
+/// The star rotates in the direction of increasing phi, so that modes with m > 0 are retrograde.
+/// As for displacement() the phi-component is the perturbation of the azimuth.
+///
+/// # Arguments:
+/// * `phase` - omega*t + psi         [rad]
+/// * `theta` - colatitude coordinate [rad]
+/// * `phi`   - azimuthal coordinate  [rad]
+/// * `l`     - degree of the spherical harmonic >= 0
+/// * `m`     - azimuthal number of the spherical harmonic, -l <= m <= l
+/// * `ampl_radial`     - amplitude in the radial direction * Y_l^m
+/// * `ampl_tangential` - amplitude in the tangential direction * Y_l^m
+/// * `spin`  - Omega/omega, with omega the angular pulsation frequency in the co-rotating frame
+/// * `ledoux` - Ledoux constant C_nl
+///
+#[allow(clippy::too_many_arguments)]
+pub fn displacement_coriolis(phase: f64, theta: f64, phi: f64, l: u16, m: i16, ampl_radial: f64, ampl_tangential: f64,
+                             spin: f64, ledoux: f64) -> (f64, f64, f64) {
+
+    let corrected = coriolis_tangential_amplitude(l, m, ampl_radial, ampl_tangential, spin, ledoux);
+    let (delta_r, delta_theta, delta_phi) = displacement(phase, theta, phi, l, m, ampl_radial, corrected);
+    let (toroidal_theta, _, toroidal_azimuth) = toroidal_displacement(phase, theta, phi, l, m, ampl_radial, ampl_tangential, spin);
+
+    return (delta_r, delta_theta + toroidal_theta, delta_phi + toroidal_azimuth)
+}
+
+
+
+
+
+
+
+
+
+/// Compute the physical (theta, phi) components of the toroidal terms of displacement_coriolis(), and the
+/// perturbation of the azimuth, i.e. the phi-component divided by sin(theta). The pole-safe recurrences are
+/// used, so that they remain finite at the poles (see displacement() for the azimuth if |m| = 1).
+///
+#[allow(clippy::too_many_arguments)]
+fn toroidal_displacement(phase: f64, theta: f64, phi: f64, l: u16, m: i16, ampl_radial: f64, ampl_tangential: f64, spin: f64) -> (f64, f64, f64) {
+
+    if spin == 0.0 {
+        return (0.0, 0.0, 0.0);
+    }
+
+    let sintheta = theta.sin();
+    let costheta = theta.cos();
+    let abs_m = m.unsigned_abs();
+    let (lf, mf) = (f64::from(l), f64::from(abs_m));
+
+    // The amplitudes tau * ampl_radial of the toroidal terms, without the factor i
+
+    let mut toroidal: Vec<(u16, f64)> = vec![(l+1, -2.0 * spin * (lf - mf + 1.0) * (ampl_radial - lf * ampl_tangential) / ((lf + 1.0) * (2.0 * lf + 1.0)))];
+    if l >= 1 && abs_m < l {
+        toroidal.push((l-1, 2.0 * spin * (lf + mf) * (ampl_radial + (lf + 1.0) * ampl_tangential) / (lf * (2.0 * lf + 1.0))));
+    }
+
+    // Re[i tau e_r x grad_h (P e^{i(phase + m phi)})] has the components
+    //     theta: tau m P / sin(theta) cos(phase + m phi)
+    //     phi:  -tau dP/dtheta sin(phase + m phi)
+
+    let (mut xi_theta, mut xi_phi, mut delta_phi) = (0.0, 0.0, 0.0);
+    for (lprime, tau) in toroidal {
+        if m != 0 {
+            xi_theta += tau * f64::from(m) * plmcos_over_sintheta(lprime, abs_m, sintheta, costheta) * f64::cos(phase + f64::from(m)*phi);
+        }
+        xi_phi -= tau * deriv1_plmcos_dtheta_polesafe(lprime, abs_m, sintheta, costheta) * f64::sin(phase + f64::from(m)*phi);
+        delta_phi -= tau * deriv1_plmcos_dtheta_over_sintheta_polesafe(lprime, abs_m, sintheta, costheta) * f64::sin(phase + f64::from(m)*phi);
+    }
+
+    return (xi_theta, xi_phi, delta_phi)
+}
+
+
+
+
+
+
+
+
+
+/// Compute the pulsation velocity vector of a mode in a slowly rotating star, i.e. the time derivative
+/// of displacement_coriolis() in the co-rotating frame. As for velocity(), the returned phi-component
+/// is the physical velocity, and the result is finite at the poles.
+///
+/// # Arguments:
+/// * `phase` - omega*t + psi         [rad]
+/// * `theta` - colatitude coordinate [rad]
+/// * `phi`   - azimuthal coordinate  [rad]
+/// * `l`     - degree of the spherical harmonic >= 0
+/// * `m`     - azimuthal number of the spherical harmonic, -l <= m <= l
+/// * `ampl_radial`     - amplitude in the radial direction * Y_l^m [km]
+/// * `ampl_tangential` - amplitude in the tangential direction * Y_l^m [km]
+/// * `spin`  - Omega/omega
+/// * `ledoux` - Ledoux constant C_nl
+/// * `omega` - angular pulsation frequency in the co-rotating frame [rad/s]
+///
+/// The result (v_r, v_theta, v_phi) is in [km/s].
+///
+#[allow(clippy::too_many_arguments)]
+pub fn velocity_coriolis(phase: f64, theta: f64, phi: f64, l: u16, m: i16, ampl_radial: f64, ampl_tangential: f64,
+                         spin: f64, ledoux: f64, omega: f64) -> (f64, f64, f64) {
+
+    let corrected = coriolis_tangential_amplitude(l, m, ampl_radial, ampl_tangential, spin, ledoux);
+    let (xi_r, xi_theta, xi_phi) = displacement_polesafe(phase + 0.5 * PI, theta, phi, l, m, ampl_radial, corrected);
+    let (toroidal_theta, toroidal_phi, _) = toroidal_displacement(phase + 0.5 * PI, theta, phi, l, m, ampl_radial, ampl_tangential, spin);
+
+    return (omega * xi_r, omega * (xi_theta + toroidal_theta), omega * (xi_phi + toroidal_phi))
+}
+
+
+
+
+
+
+
+
+
+
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn test_velocity_is_time_derivative() {
+
+        // Compare with a central difference of the displacement w.r.t. time
+
+        let (omega, t, dt) = (1.0e-4, 2500.0, 1.0e-2);
+        let (theta, phi) = (1.1, 0.4);
+        for m in [-2, 0, 1, 3] {
+            let (v_r, v_theta, v_phi) = velocity(omega * t, theta, phi, 3, m, 10.0, 4.0, omega);
+            let before = displacement(omega * (t - dt), theta, phi, 3, m, 10.0, 4.0);
+            let after  = displacement(omega * (t + dt), theta, phi, 3, m, 10.0, 4.0);
+            assert_approx_eq!(v_r, (after.0 - before.0) / (2.0 * dt), 1.0e-9);
+            assert_approx_eq!(v_theta, (after.1 - before.1) / (2.0 * dt), 1.0e-9);
+            assert_approx_eq!(v_phi, theta.sin() * (after.2 - before.2) / (2.0 * dt), 1.0e-9);
+        }
+    }
+
+    #[test]
+    fn test_displacement_negative_m() {
+
+        // The theta-component is the derivative of P_l^|m|, also for m < 0, so that the displacement
+        // of the mode -m equals that of the mode m mirrored in phi
+
+        let (phase, theta, phi, step) = (0.3, 1.1_f64, 0.4, 1.0e-6);
+        for m in [-3_i16, -2, -1] {
+            let abs_m = m.unsigned_abs();
+            let derivative = (plmcos(3, abs_m, (theta + step).sin(), (theta + step).cos())
+                              - plmcos(3, abs_m, (theta - step).sin(), (theta - step).cos())) / (2.0 * step);
+            let (delta_r, delta_theta, delta_phi) = displacement(phase, theta, phi, 3, m, 1.0, 0.2);
+            let mirrored = displacement(phase, theta, -phi, 3, -m, 1.0, 0.2);
+            assert_approx_eq!(delta_theta, 0.2 * derivative * f64::cos(phase + f64::from(m) * phi), 1.0e-8);
+            assert_approx_eq!(delta_r, mirrored.0, 1.0e-14);
+            assert_approx_eq!(delta_theta, mirrored.1, 1.0e-14);
+            assert_approx_eq!(delta_phi, -mirrored.2, 1.0e-14);
+        }
+    }
+
+    #[test]
+    fn test_displacement_coriolis() {
+
+        // Without rotation there are no toroidal terms
+
+        let plain = displacement(0.4, 1.2, 2.0, 3, -2, 1.0, 0.2);
+        let rotating = displacement_coriolis(0.4, 1.2, 2.0, 3, -2, 1.0, 0.2, 0.0, 0.3);
+        assert_approx_eq!(plain.1, rotating.1, 1.0e-14);
+        assert_approx_eq!(plain.2, rotating.2, 1.0e-14);
+
+        // The toroidal terms of a radial mode only come from l' = 1, m = 0: tau_1 = -2 spin / 1,
+        // so that xi_phi = 2 spin dP_1^0/dtheta sin(phase) = -2 spin sin(theta) sin(phase).
+
+        let (theta, phase, spin) = (0.7, 0.3, 0.05);
+        let (_, delta_theta, delta_phi) = displacement_coriolis(phase, theta, 0.0, 0, 0, 1.0, 0.0, spin, 0.0);
+        assert_approx_eq!(delta_theta, 0.0, 1.0e-14);
+        assert_approx_eq!(delta_phi * theta.sin(), -2.0 * spin * theta.sin() * phase.sin(), 1.0e-14);
+    }
+
+    #[test]
+    fn test_coriolis_tangential_amplitude() {
+
+        // For high-order g modes, with C_nl = 1/(l(l+1)) and K >> 1, the Coriolis correction of K tends to
+        // 2 m Omega/omega / (l(l+1)) (Schrijvers et al. 1997), independent of K
+
+        let (l, m, spin) = (2_u16, 1_i16, 0.05);
+        let ledoux = 1.0 / 6.0;
+        for k in [1.0e3, 1.0e5] {
+            let corrected = coriolis_tangential_amplitude(l, m, 1.0, k, spin, ledoux);
+            assert_approx_eq!(corrected - k, 2.0 * spin / 6.0, 2.0 * spin / k);
+        }
+
+        // The correction is antisymmetric in m, and in the displacement only the spheroidal part changes with C_nl
+
+        let plus = coriolis_tangential_amplitude(3, 2, 1.0, 0.4, spin, 0.2) - 0.4;
+        let minus = coriolis_tangential_amplitude(3, -2, 1.0, 0.4, spin, 0.2) - 0.4;
+        assert_approx_eq!(plus, -minus, 1.0e-15);
+        assert_approx_eq!(plus, 4.0 * spin * (1.4 / 12.0 - 0.2 * 0.4), 1.0e-15);
+
+        let (phase, theta, phi) = (0.3, 1.1, 0.7);
+        let with_ledoux = displacement_coriolis(phase, theta, phi, 3, 2, 1.0, 0.4, spin, 0.2);
+        let without_ledoux = displacement_coriolis(phase, theta, phi, 3, 2, 1.0, 0.4, spin, 0.0);
+        let unit = displacement(phase, theta, phi, 3, 2, 0.0, 1.0);
+        assert_approx_eq!(with_ledoux.0, without_ledoux.0, 1.0e-15);
+        assert_approx_eq!(with_ledoux.1 - without_ledoux.1, -4.0 * spin * 0.2 * 0.4 * unit.1, 1.0e-14);
+        assert_approx_eq!(with_ledoux.2 - without_ledoux.2, -4.0 * spin * 0.2 * 0.4 * unit.2, 1.0e-14);
+    }
+
+    #[test]
+    fn test_perturbed_normal_and_area() {
+
+        // Displace the points of the unit sphere and compare with the normal and the area of the
+        // displaced surface, obtained with central differences
+
+        let (phase, l, m, ampl_radial, ampl_tangential) = (0.6, 3_u16, 2_i16, 1.0e-5, 3.0e-6);
+        let position = |theta: f64, phi: f64| -> [f64; 3] {
+            let (xi_r, xi_theta, xi_phi) = displacement_polesafe(phase, theta, phi, l, m, ampl_radial, ampl_tangential);
+            let (e_r, e_theta, e_phi) = ([theta.sin() * phi.cos(), theta.sin() * phi.sin(), theta.cos()],
+                                         [theta.cos() * phi.cos(), theta.cos() * phi.sin(), -theta.sin()],
+                                         [-phi.sin(), phi.cos(), 0.0]);
+            [0, 1, 2].map(|j| (1.0 + xi_r) * e_r[j] + xi_theta * e_theta[j] + xi_phi * e_phi[j])
+        };
+
+        let (theta, phi, step) = (1.1_f64, 0.4_f64, 1.0e-5);
+        let dtheta = [0, 1, 2].map(|j| (position(theta + step, phi)[j] - position(theta - step, phi)[j]) / (2.0 * step));
+        let dphi = [0, 1, 2].map(|j| (position(theta, phi + step)[j] - position(theta, phi - step)[j]) / (2.0 * step));
+        let normal = [dtheta[1] * dphi[2] - dtheta[2] * dphi[1], dtheta[2] * dphi[0] - dtheta[0] * dphi[2], dtheta[0] * dphi[1] - dtheta[1] * dphi[0]];
+        let area = (normal[0] * normal[0] + normal[1] * normal[1] + normal[2] * normal[2]).sqrt();
+
+        let (n_r, n_theta, n_phi) = perturbed_normal_mode(phase, theta, phi, l, m, ampl_radial, ampl_tangential);
+        let e_theta = [theta.cos() * phi.cos(), theta.cos() * phi.sin(), -theta.sin()];
+        let e_phi = [-phi.sin(), phi.cos(), 0.0];
+        assert_approx_eq!(n_r, 1.0, 1.0e-14);
+        assert_approx_eq!(n_theta, (0..3).map(|j| normal[j] * e_theta[j]).sum::<f64>() / area, 1.0e-8);
+        assert_approx_eq!(n_phi, (0..3).map(|j| normal[j] * e_phi[j]).sum::<f64>() / area, 1.0e-8);
+        assert_approx_eq!(relative_area_change_mode(phase, theta, phi, l, m, ampl_radial, ampl_tangential), area / theta.sin() - 1.0, 1.0e-8);
+
+        // The normal and the area change are linear in the displacement, so that they can be computed from the summed field
+
+        let second = (1.3, 2_u16, -1_i16, 2.0e-6, 4.0e-6);
+        let total = [displacement_polesafe(phase, theta, phi, l, m, ampl_radial, ampl_tangential),
+                     displacement_polesafe(second.0, theta, phi, second.1, second.2, second.3, second.4)];
+        let gradient = [displacement_polesafe(phase, theta, phi, l, m, 0.0, ampl_radial),
+                        displacement_polesafe(second.0, theta, phi, second.1, second.2, 0.0, second.3)];
+        let summed = perturbed_normal((total[0].0 + total[1].0, total[0].1 + total[1].1, total[0].2 + total[1].2),
+                                      gradient[0].1 + gradient[1].1, gradient[0].2 + gradient[1].2);
+        let separate = perturbed_normal_mode(second.0, theta, phi, second.1, second.2, second.3, second.4);
+        assert_approx_eq!(summed.1, n_theta + separate.1, 1.0e-15);
+        assert_approx_eq!(summed.2, n_phi + separate.2, 1.0e-15);
+
+        // The horizontal divergence agrees with the Laplacian computed with deriv2_plmcos_dtheta()
+
+        let (sintheta, costheta) = (theta.sin(), theta.cos());
+        let laplacian = deriv2_plmcos_dtheta(l, 2, sintheta, costheta) + costheta / sintheta * deriv1_plmcos_dtheta(l, 2, sintheta, costheta)
+                        - 4.0 / (sintheta * sintheta) * plmcos(l, 2, sintheta, costheta);
+        assert_approx_eq!(laplacian, -12.0 * plmcos(l, 2, sintheta, costheta), 1.0e-8);
+    }
+
+    #[test]
+    fn test_relative_area_change_at_the_poles() {
+
+        // The divergence computed with the derivatives equals -l(l+1) ampl_tangential P_l^|m| cos(phase + m phi),
+        // also exactly at the poles
+
+        let (phase, phi) = (0.5, 0.9);
+        for theta in [0.0, 0.3, 2.0, PI] {
+            for m in [-2_i16, -1, 0, 1, 2, 3] {
+                let plm = plmcos(3, m.unsigned_abs(), theta.sin(), theta.cos());
+                let expected = (2.0 * 1.0e-5 - 12.0 * 4.0e-6) * plm * f64::cos(phase + f64::from(m) * phi);
+                assert_approx_eq!(relative_area_change_mode(phase, theta, phi, 3, m, 1.0e-5, 4.0e-6), expected, 1.0e-15);
+            }
+        }
+    }
+
+    #[test]
+    fn test_displacement_at_the_poles() {
+
+        // Exactly at the poles the displacement equals the limit along the meridian phi, also with
+        // the toroidal terms, except for the azimuth perturbation of |m| = 1, which diverges
+
+        let (phase, phi, spin) = (0.7, 0.4, 0.1);
+        for theta in [0.0, PI] {
+            let near = if theta == 0.0 { 1.0e-9 } else { PI - 1.0e-9 };
+            for m in [-3, -2, -1, 0, 1, 2, 3] {
+                let at_pole = displacement_coriolis(phase, theta, phi, 3, m, 1.0, 0.4, spin, 0.1);
+                let close_to_pole = displacement_coriolis(phase, near, phi, 3, m, 1.0, 0.4, spin, 0.1);
+                assert!(at_pole.0.is_finite() && at_pole.1.is_finite() && at_pole.2.is_finite());
+                assert_approx_eq!(at_pole.0, close_to_pole.0, 1.0e-6);
+                assert_approx_eq!(at_pole.1, close_to_pole.1, 1.0e-6);
+                if m.abs() == 1 {
+                    assert_eq!(at_pole.2, 0.0);
+                    let physical = displacement_polesafe(phase, theta, phi, 3, m, 1.0, 0.4);
+                    let (_, _, delta_phi) = displacement(phase, near, phi, 3, m, 1.0, 0.4);
+                    assert_approx_eq!(delta_phi * near.sin(), physical.2, 1.0e-6);
+                } else {
+                    assert_approx_eq!(at_pole.2, close_to_pole.2, 1.0e-6);
+                }
+
+                let plain = displacement(phase, theta, phi, 3, m, 1.0, 0.4);
+                assert!(plain.0.is_finite() && plain.1.is_finite() && plain.2.is_finite());
+            }
+        }
+
+        // For m = 2 the azimuth perturbation at the pole is -2 K P_l^2 / sin^2(theta) sin(phase + 2 phi)
+
+        let (_, _, delta_phi) = displacement(phase, 0.0, phi, 3, 2, 1.0, 0.4);
+        assert_approx_eq!(delta_phi, -2.0 * 0.4 * 15.0 * f64::sin(phase + 2.0 * phi), 1.0e-12);
+    }
+
+    #[test]
+    fn test_velocity_at_the_poles() {
+
+        // At the poles the velocity equals the limit along the meridian phi
+
+        let (omega, phase, phi, spin) = (1.0e-4, 0.7, 0.4, 0.1);
+        for theta in [0.0, PI] {
+            let near = if theta == 0.0 { 1.0e-7 } else { PI - 1.0e-7 };
+            for m in [-2, -1, 0, 1, 3] {
+                let at_pole = velocity_coriolis(phase, theta, phi, 3, m, 10.0, 4.0, spin, 0.1, omega);
+                let close_to_pole = velocity_coriolis(phase, near, phi, 3, m, 10.0, 4.0, spin, 0.1, omega);
+                assert!(at_pole.0.is_finite() && at_pole.1.is_finite() && at_pole.2.is_finite());
+                assert_approx_eq!(at_pole.0, close_to_pole.0, 1.0e-8);
+                assert_approx_eq!(at_pole.1, close_to_pole.1, 1.0e-8);
+                assert_approx_eq!(at_pole.2, close_to_pole.2, 1.0e-8);
+            }
+        }
+    }
+}
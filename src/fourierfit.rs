@@ -0,0 +1,454 @@
+use std::f64::consts::PI;
+use num_complex::Complex;
+use crate::auxilliary::*;
+use crate::mode::*;
+use crate::lineprofile::*;
+
+
+// The Fourier parameter fit (Zima 2006, A&A 455, 227) describes the variation of the flux in each velocity
+// bin of a time series of line profiles with a sum of sinusoids at the pulsation frequencies,
+//     F(v, t) = F_0(v) + \sum_j A_j(v) cos(2 pi f_j t + phi_j(v))
+// and compares the amplitude and phase distributions A_j(v) and phi_j(v) across the profile with those of
+// synthetic profiles. Phases are in radians, and the uncertainties follow Montgomery & O'Donoghue (1999):
+//     sigma_A = sqrt(2/N) sigma,   sigma_phi = sigma_A / A
+// with sigma the standard deviation of the residuals of the N fluxes in the bin.
+
+
+
+
+
+/// The amplitude and phase distribution across the line profile at one pulsation frequency
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct FourierParameters {
+    pub frequency: f64,              // Pulsation frequency [c/d]
+    pub amplitudes: Vec<f64>,        // A(v) per velocity bin, in units of the continuum
+    pub phases: Vec<f64>,            // phi(v) per velocity bin [rad]
+    pub amplitude_errors: Vec<f64>,  // 1-sigma uncertainties of A(v)
+    pub phase_errors: Vec<f64>,      // 1-sigma uncertainties of phi(v) [rad]
+}
+
+
+
+
+
+
+
+
+
+/// Fits the flux in each velocity bin of a time series of line profiles with sinusoids at the given
+/// frequencies, and returns the amplitude and phase distributions at each frequency
+///
+/// # Arguments
+///
+/// * `times`       - times of the observations [d]
+/// * `profiles`    - the continuum-normalised profiles, one for each time, on a common velocity grid
+/// * `frequencies` - the pulsation frequencies [c/d]
+///
+/// Returns None if the frequencies cannot be fitted simultaneously, e.g. because they are not resolved by
+/// the time base or because there are too few observations.
+///
+pub fn fourier_parameters(times: &[f64], profiles: &[Vec<f64>], frequencies: &[f64]) -> Option<Vec<FourierParameters>> {
+
+    assert!(times.len() == profiles.len(), "fourier_parameters(): times and profiles differ in length");
+    assert!(!profiles.is_empty() && profiles.iter().all(|profile| profile.len() == profiles[0].len()),
+            "fourier_parameters(): profiles differ in length");
+
+    let n_times = times.len();
+    if n_times <= 2 * frequencies.len() {
+        return None;
+    }
+    let degrees_of_freedom = n_times.saturating_sub(2 * frequencies.len() + 1).max(1);
+
+    let mut parameters: Vec<FourierParameters> = frequencies.iter().map(|frequency| {
+        FourierParameters { frequency: *frequency, amplitudes: vec![], phases: vec![], amplitude_errors: vec![], phase_errors: vec![] }
+    }).collect();
+
+    for bin in 0..profiles[0].len() {
+        let fluxes: Vec<f64> = profiles.iter().map(|profile| profile[bin]).collect();
+        let (constant, coefficients) = fit_sinusoids(times, &fluxes, frequencies)?;
+
+        // The standard deviation of the residuals of the fit
+
+        let mut sum_of_squares: f64 = 0.0;
+        for (time, flux) in times.iter().zip(fluxes.iter()) {
+            let model: f64 = frequencies.iter().zip(coefficients.iter())
+                                        .map(|(frequency, (a, b))| a * (2.0 * PI * frequency * time).cos() + b * (2.0 * PI * frequency * time).sin())
+                                        .sum();
+            sum_of_squares += (flux - constant - model).powi(2);
+        }
+        let amplitude_error = (2.0 / n_times as f64).sqrt() * (sum_of_squares / degrees_of_freedom as f64).sqrt();
+
+        // a cos(x) + b sin(x) = A cos(x + phi) with A e^{i phi} = a - i b
+
+        for (parameters, (a, b)) in parameters.iter_mut().zip(coefficients.iter()) {
+            let (amplitude, phase) = Complex::new(*a, -*b).to_polar();
+            parameters.amplitudes.push(amplitude);
+            parameters.phases.push(phase);
+            parameters.amplitude_errors.push(amplitude_error);
+            parameters.phase_errors.push(if amplitude > 0.0 { amplitude_error / amplitude } else { f64::INFINITY });
+        }
+    }
+
+    return Some(parameters);
+}
+
+
+
+
+
+
+
+
+
+/// The best solution of the Fourier parameter fit for one candidate mode (l, m)
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FourierSolution {
+    pub l: u16,                      // Degree of the candidate mode
+    pub m: i16,                      // Azimuthal number of the candidate mode
+    pub inclination: f64,            // Angle between the rotation axis and the line of sight [rad]
+    pub vsini: f64,                  // Projected equatorial rotation velocity [km/s]
+    pub velocity_amplitude: f64,     // Amplitude omega * ampl_radial * R of the pulsation velocity [km/s]
+    pub phase_offset: f64,           // Observed minus synthetic phase, common to all bins [rad]
+    pub chi2_amplitude: f64,         // Reduced chi-square of the amplitude distribution
+    pub chi2_phase: f64,             // Reduced chi-square of the phase distribution
+    pub chi2: f64,                   // Mean of both
+}
+
+
+
+
+
+
+
+
+
+/// Mode identification of a single pulsation frequency with the Fourier parameter fit
+///
+/// For each candidate mode with 0 <= l <= lmax and -l <= m <= l, and for all combinations of the inclinations,
+/// v sin i and velocity amplitudes of the grids, profiles are synthesised with the LineProfileSynthesizer
+/// at `n_phases` equidistant phases of one pulsation cycle, which suffices for a single mode, and their
+/// Fourier parameters are compared with the observed ones. The ratio of the tangential to the radial
+/// amplitude is the adiabatic one for the mass of the synthesizer. Since the phase of the mode is
+/// arbitrary, the synthetic phases are shifted over the weighted circular mean of the phase differences.
+///
+/// The intrinsic profile, limb darkening law, radius, Teff, log g and surface grid are taken from the
+/// synthesizer, whose own inclination and rotation velocity are not used nor modified. Pole-on grid points
+/// with v sin i > 0 are impossible and skipped, as are grid points whose chi-square is not finite.
+///
+/// The observed amplitude uncertainties are raised to at least `error_floor` times the largest observed
+/// amplitude, so that bins with vanishing residuals, e.g. of noise-free synthetic observations, do not get
+/// an infinite weight. Bins whose uncertainty is still zero, because nothing varies, are skipped.
+///
+pub struct FourierParameterFit {
+    pub velocities: Vec<f64>,                 // Velocity grid of the profiles [km/s]
+    pub observed: FourierParameters,          // The observed amplitude and phase distribution
+    pub synthesizer: LineProfileSynthesizer,
+    pub lmax: u16,                            // Maximum degree of the candidate modes
+    pub n_phases: usize,                      // Number of synthetic profiles per pulsation cycle
+    pub inclinations: Vec<f64>,               // [rad]
+    pub vsinis: Vec<f64>,                     // [km/s]
+    pub velocity_amplitudes: Vec<f64>,        // [km/s]
+    pub error_floor: f64,                     // Minimum amplitude uncertainty, relative to the largest observed amplitude
+}
+
+
+
+
+
+
+
+
+
+impl FourierParameterFit {
+
+    /// Creates a fit with candidate modes up to l = 4, 16 synthetic profiles per cycle, inclinations
+    /// from 5 to 90 degrees in steps of 5 degrees, and an error floor of 1e-3. The grids of v sin i and velocity amplitudes have to be
+    /// set with with_vsini() and with_velocity_amplitudes().
+    ///
+    /// # Arguments
+    ///
+    /// * `velocities`  - velocity grid of the observed profiles [km/s]
+    /// * `observed`    - the observed Fourier parameters, e.g. from fourier_parameters()
+    /// * `synthesizer` - the synthesizer of the theoretical profiles
+    ///
+    pub fn new(velocities: Vec<f64>, observed: FourierParameters, synthesizer: LineProfileSynthesizer) -> Self {
+
+        assert!(velocities.len() == observed.amplitudes.len(), "FourierParameterFit::new(): velocities and Fourier parameters differ in length");
+
+        let inclinations = (1..=18).map(|j| f64::from(5 * j).to_radians()).collect();
+
+        FourierParameterFit { velocities, observed, synthesizer, lmax: 4, n_phases: 16, inclinations, vsinis: vec![], velocity_amplitudes: vec![],
+                              error_floor: 1.0e-3 }
+    }
+
+
+
+
+    /// Sets the maximum degree of the candidate modes
+    ///
+    pub fn with_lmax(mut self, lmax: u16) -> Self {
+        self.lmax = lmax;
+        self
+    }
+
+
+
+
+    /// Sets the number of synthetic profiles per pulsation cycle
+    ///
+    pub fn with_phases(mut self, n_phases: usize) -> Self {
+        self.n_phases = n_phases;
+        self
+    }
+
+
+
+
+    /// Sets the grid of inclinations [rad]
+    ///
+    pub fn with_inclinations(mut self, inclinations: Vec<f64>) -> Self {
+        self.inclinations = inclinations;
+        self
+    }
+
+
+
+
+    /// Sets the grid of projected equatorial rotation velocities [km/s]
+    ///
+    pub fn with_vsini(mut self, vsinis: Vec<f64>) -> Self {
+        self.vsinis = vsinis;
+        self
+    }
+
+
+
+
+    /// Sets the grid of pulsation velocity amplitudes omega * ampl_radial * R [km/s]
+    ///
+    pub fn with_velocity_amplitudes(mut self, velocity_amplitudes: Vec<f64>) -> Self {
+        self.velocity_amplitudes = velocity_amplitudes;
+        self
+    }
+
+
+
+
+    /// Sets the minimum amplitude uncertainty, relative to the largest observed amplitude
+    ///
+    pub fn with_error_floor(mut self, error_floor: f64) -> Self {
+        assert!(error_floor >= 0.0, "FourierParameterFit::with_error_floor(): error floor < 0");
+        self.error_floor = error_floor;
+        self
+    }
+
+
+
+
+    /// Scans all candidate modes and grid points, and returns the best solution of each candidate (l, m),
+    /// ranked from the lowest to the highest chi-square. Candidates without any valid grid point are left out.
+    ///
+    pub fn search(&self) -> Vec<FourierSolution> {
+
+        assert!(!self.inclinations.is_empty() && !self.vsinis.is_empty() && !self.velocity_amplitudes.is_empty(),
+                "FourierParameterFit::search(): empty parameter grid");
+        assert!(self.n_phases >= 3, "FourierParameterFit::search(): need at least 3 phases");
+
+        let frequency = self.observed.frequency;
+        let omega = 2.0 * PI * frequency / SECONDS_PER_DAY;
+        let radius = self.synthesizer.radius;
+        let k = Mode::adiabatic_k(frequency, self.synthesizer.mass(), radius);
+        let times: Vec<f64> = (0..self.n_phases).map(|j| j as f64 / (self.n_phases as f64 * frequency)).collect();
+
+        let mut solutions = Vec::new();
+        for l in 0..=self.lmax {
+            for m in -(l as i16)..=(l as i16) {
+
+                let mut best: Option<FourierSolution> = None;
+                for &inclination in self.inclinations.iter() {
+                    for &vsini in self.vsinis.iter() {
+                        for &velocity_amplitude in self.velocity_amplitudes.iter() {
+
+                            let v_eq = if vsini > 0.0 { vsini / inclination.sin() } else { 0.0 };
+                            if !v_eq.is_finite() {
+                                continue;
+                            }
+                            let ampl_radial = velocity_amplitude / (omega * radius * SOLAR_RADIUS_KM);
+                            let modes = ModeSet::from(vec![Mode::new(l, m, frequency, ampl_radial, k, 0.0)]);
+
+                            let profiles: Vec<Vec<f64>> = times.iter()
+                                                              .map(|time| self.synthesizer.profile_seen_from(&modes, *time, &self.velocities, inclination, v_eq))
+                                                              .collect();
+                            let Some(synthetic) = fourier_parameters(&times, &profiles, &[frequency]).and_then(|mut parameters| parameters.pop()) else {
+                                continue;
+                            };
+
+                            let (phase_offset, chi2_amplitude, chi2_phase) = self.chi_square(&synthetic);
+                            let chi2 = 0.5 * (chi2_amplitude + chi2_phase);
+                            if chi2.is_finite() && best.is_none_or(|solution| chi2 < solution.chi2) {
+                                best = Some(FourierSolution { l, m, inclination, vsini, velocity_amplitude, phase_offset,
+                                                              chi2_amplitude, chi2_phase, chi2 });
+                            }
+                        }
+                    }
+                }
+                if let Some(best) = best {
+                    solutions.push(best);
+                }
+            }
+        }
+
+        solutions.sort_by(|a, b| a.chi2.total_cmp(&b.chi2));
+
+        return solutions;
+    }
+
+
+
+
+    /// Computes the phase offset and the reduced chi-squares of the amplitude and phase distributions
+    /// between the observed and the synthetic Fourier parameters
+    ///
+    fn chi_square(&self, synthetic: &FourierParameters) -> (f64, f64, f64) {
+
+        let observed = &self.observed;
+
+        // The uncertainties with the error floor, sigma_phi = sigma_A / A, and the bins that can be used
+
+        let floor = self.error_floor * observed.amplitudes.iter().fold(0.0_f64, |maximum, amplitude| maximum.max(*amplitude));
+        let bins: Vec<(usize, f64, f64)> = (0..observed.amplitudes.len()).filter_map(|j| {
+            let amplitude_error = observed.amplitude_errors[j].max(floor);
+            let phase_error = if observed.amplitudes[j] > 0.0 { amplitude_error / observed.amplitudes[j] } else { f64::INFINITY };
+            if amplitude_error > 0.0 { Some((j, amplitude_error, phase_error)) } else { None }
+        }).collect();
+
+        if bins.is_empty() {
+            return (0.0, 0.0, 0.0);
+        }
+
+        // The weighted circular mean of the phase differences
+
+        let offset = bins.iter()
+                         .map(|(j, _, phase_error)| Complex::from_polar(1.0 / (phase_error * phase_error), observed.phases[*j] - synthetic.phases[*j]))
+                         .sum::<Complex<f64>>()
+                         .arg();
+
+        let mut chi2_amplitude: f64 = 0.0;
+        let mut chi2_phase: f64 = 0.0;
+        for (j, amplitude_error, phase_error) in bins.iter() {
+            chi2_amplitude += ((observed.amplitudes[*j] - synthetic.amplitudes[*j]) / amplitude_error).powi(2);
+            let difference = Complex::from_polar(1.0, observed.phases[*j] - synthetic.phases[*j] - offset).arg();
+            chi2_phase += (difference / phase_error).powi(2);
+        }
+
+        return (offset, chi2_amplitude / bins.len() as f64, chi2_phase / bins.len() as f64);
+    }
+}
+
+
+
+
+
+
+
+
+
+
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::limbdarkening::*;
+    use crate::intrinsicprofile::*;
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn test_fourier_parameters_of_sinusoids() {
+
+        // Two bins with two frequencies of known amplitude and phase
+
+        let frequencies = [3.1, 7.4];
+        let times: Vec<f64> = (0..60).map(|n| 0.037 * n as f64 + 0.01 * (n as f64).sin()).collect();
+        let profiles: Vec<Vec<f64>> = times.iter().map(|t| {
+            vec![0.9 + 0.02 * (2.0 * PI * 3.1 * t + 0.4).cos() + 0.01 * (2.0 * PI * 7.4 * t - 2.0).cos(),
+                 0.8 + 0.03 * (2.0 * PI * 3.1 * t - 1.0).cos()]
+        }).collect();
+
+        let parameters = fourier_parameters(&times, &profiles, &frequencies).unwrap();
+        assert_approx_eq!(parameters[0].amplitudes[0], 0.02, 1.0e-12);
+        assert_approx_eq!(parameters[0].phases[0], 0.4, 1.0e-10);
+        assert_approx_eq!(parameters[1].amplitudes[0], 0.01, 1.0e-12);
+        assert_approx_eq!(parameters[1].phases[0], -2.0, 1.0e-10);
+        assert_approx_eq!(parameters[0].phases[1], -1.0, 1.0e-10);
+        assert_approx_eq!(parameters[1].amplitudes[1], 0.0, 1.0e-12);
+
+        // Coinciding frequencies cannot be fitted simultaneously
+
+        assert!(fourier_parameters(&times, &profiles, &[3.1, 3.1]).is_none());
+        assert!(fourier_parameters(&times[..4], &profiles[..4], &frequencies).is_none());
+    }
+
+    // Profiles of an l = 2, m = -1 mode with i = 1 rad, v sin i = 15 km/s and a velocity amplitude of 8 km/s,
+    // and the fit of the three lowest degrees on a coarse grid of inclinations
+
+    fn search(noise: f64, inclinations: Vec<f64>) -> Vec<FourierSolution> {
+
+        let synthesizer = || LineProfileSynthesizer::new(1.0, 4.0, 15000.0, 4.0, GaussianProfile::new(5.0, 0.4), LinearLaw { u: 0.5 })
+                              .with_grid(20, 40);
+        let velocities: Vec<f64> = (0..=40).map(|j| -40.0 + 2.0 * j as f64).collect();
+
+        let (frequency, inclination, vsini, velocity_amplitude) = (6.3, 1.0_f64, 15.0, 8.0);
+        let mut observer = synthesizer().with_rotation(vsini / inclination.sin());
+        observer.inclination = inclination;
+        let ampl_radial = velocity_amplitude / (2.0 * PI * frequency / SECONDS_PER_DAY * 4.0 * SOLAR_RADIUS_KM);
+        let modes = ModeSet::from(vec![Mode::new(2, -1, frequency, ampl_radial, Mode::adiabatic_k(frequency, observer.mass(), 4.0), 0.8)]);
+        let times: Vec<f64> = (0..30).map(|n| 0.013 * n as f64).collect();
+        let profiles: Vec<Vec<f64>> = times.iter().enumerate().map(|(n, time)| {
+            observer.profile(&modes, *time, &velocities).iter().enumerate().map(|(j, flux)| flux + noise * (1.0e3 * (n * 41 + j) as f64).sin()).collect()
+        }).collect();
+        let observed = fourier_parameters(&times, &profiles, &[frequency]).unwrap().pop().unwrap();
+
+        return FourierParameterFit::new(velocities, observed, synthesizer())
+               .with_lmax(2)
+               .with_phases(8)
+               .with_inclinations(inclinations)
+               .with_vsini(vec![15.0])
+               .with_velocity_amplitudes(vec![4.0, 8.0])
+               .search();
+    }
+
+    #[test]
+    fn test_search_recovers_the_mode() {
+
+        // With a little deterministic noise
+
+        let solutions = search(1.0e-4, vec![0.6, 1.0]);
+        assert_eq!(solutions.len(), 9);
+        assert_eq!((solutions[0].l, solutions[0].m), (2, -1));
+        assert_approx_eq!(solutions[0].inclination, 1.0, 1.0e-12);
+        assert_approx_eq!(solutions[0].velocity_amplitude, 8.0, 1.0e-12);
+        assert_approx_eq!(solutions[0].phase_offset, 0.8, 1.0e-2);
+        assert!(solutions[0].chi2 < 2.0 && solutions[1].chi2 > 2.0 * solutions[0].chi2);
+    }
+
+    #[test]
+    fn test_search_without_noise() {
+
+        // Noise-free profiles leave no residuals, so that the error floor sets the uncertainties. The pole-on
+        // inclination cannot have v sin i > 0 and is skipped.
+
+        let solutions = search(0.0, vec![0.0, 0.6, 1.0]);
+        assert_eq!(solutions.len(), 9);
+        assert!(solutions.iter().all(|solution| solution.chi2.is_finite() && solution.phase_offset.is_finite()));
+        assert_eq!((solutions[0].l, solutions[0].m), (2, -1));
+        assert_approx_eq!(solutions[0].inclination, 1.0, 1.0e-12);
+        assert_approx_eq!(solutions[0].velocity_amplitude, 8.0, 1.0e-12);
+        assert_approx_eq!(solutions[0].phase_offset, 0.8, 1.0e-2);
+        assert!(solutions[1].chi2 > 2.0 * solutions[0].chi2);
+    }
+}
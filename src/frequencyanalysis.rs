@@ -0,0 +1,574 @@
+use std::f64::consts::PI;
+use num_complex::Complex;
+use crate::auxilliary::*;
+
+
+// All sinusoids are written as A cos(2 pi f t + phi), with the frequency f in c/d, the times t in days and
+// the phase phi in radians, i.e. with the convention of the phase psi of a Mode, so that an extracted
+// sinusoid can be turned into a Mode with Mode::new(l, m, sinusoid.frequency, ..., sinusoid.phase).
+// Note that the phase of the pulsation velocity is shifted over a quarter of a cycle w.r.t. the displacement.
+
+
+
+
+
+/// A sinusoid A cos(2 pi f t + phi)
+///
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Sinusoid {
+    pub frequency: f64,              // f [c/d]
+    pub amplitude: f64,              // A, in the units of the time series
+    pub phase: f64,                  // phi at t = 0 [rad]
+}
+
+
+
+
+impl Sinusoid {
+
+    /// Returns the value of the sinusoid at time `time` [d]
+    ///
+    pub fn value(&self, time: f64) -> f64 {
+        return self.amplitude * (2.0 * PI * self.frequency * time + self.phase).cos();
+    }
+}
+
+
+
+
+
+
+
+
+
+/// The generalised Lomb-Scargle periodogram of a time series
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct Periodogram {
+    pub frequencies: Vec<f64>,       // [c/d]
+    pub power: Vec<f64>,             // Normalised power 0 <= p <= 1, the fraction of the variance explained
+    pub amplitudes: Vec<f64>,        // Amplitude A of the best-fitting sinusoid, in the units of the time series
+}
+
+
+
+
+
+
+
+
+
+/// Computes the generalised Lomb-Scargle periodogram of Zechmeister & Kurster (2009, A&A 496, 577),
+/// which fits a sinusoid plus a constant at each frequency, so that the mean of the time series is
+/// fitted along ("floating mean") rather than subtracted beforehand. All observations get equal weights.
+///
+/// # Arguments
+///
+/// * `times`       - times of the observations, not necessarily evenly spaced [d]
+/// * `values`      - the observed values, e.g. radial velocities, moments or magnitudes
+/// * `frequencies` - the frequencies at which the periodogram is computed [c/d]
+///
+pub fn lomb_scargle(times: &[f64], values: &[f64], frequencies: &[f64]) -> Periodogram {
+
+    assert!(times.len() == values.len(), "lomb_scargle(): times and values differ in length");
+    assert!(times.len() > 2, "lomb_scargle(): need at least 3 observations");
+
+    let weight = 1.0 / times.len() as f64;
+    let mean: f64 = values.iter().sum::<f64>() * weight;
+    let variance: f64 = values.iter().map(|y| (y - mean) * (y - mean)).sum::<f64>() * weight;
+
+    let mut power = Vec::with_capacity(frequencies.len());
+    let mut amplitudes = Vec::with_capacity(frequencies.len());
+    for frequency in frequencies.iter() {
+
+        // The weighted sums of Zechmeister & Kurster, with the mean of the data already subtracted
+
+        let (mut c, mut s, mut yc, mut ys, mut cc, mut ss, mut cs) = (0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0);
+        for (time, value) in times.iter().zip(values.iter()) {
+            let (sine, cosine) = (2.0 * PI * frequency * time).sin_cos();
+            let y = value - mean;
+            c += cosine;
+            s += sine;
+            yc += y * cosine;
+            ys += y * sine;
+            cc += cosine * cosine;
+            ss += sine * sine;
+            cs += cosine * sine;
+        }
+        let (c, s, yc, ys) = (c * weight, s * weight, yc * weight, ys * weight);
+        let cc = cc * weight - c * c;
+        let ss = ss * weight - s * s;
+        let cs = cs * weight - c * s;
+        let determinant = cc * ss - cs * cs;
+
+        if determinant <= 0.0 || variance == 0.0 {
+            power.push(0.0);
+            amplitudes.push(0.0);
+            continue;
+        }
+
+        power.push((ss * yc * yc + cc * ys * ys - 2.0 * cs * yc * ys) / (variance * determinant));
+        let a = (yc * ss - ys * cs) / determinant;
+        let b = (ys * cc - yc * cs) / determinant;
+        amplitudes.push((a * a + b * b).sqrt());
+    }
+
+    return Periodogram { frequencies: frequencies.to_vec(), power, amplitudes };
+}
+
+
+
+
+
+
+
+
+
+/// Computes the amplitude spectral window |\sum_n e^{2 pi i f t_n}| / N of the sampling, which equals 1 at f = 0.
+/// Each peak in the periodogram is accompanied by a copy of the window around it (aliases).
+///
+/// # Arguments
+///
+/// * `times`       - times of the observations [d]
+/// * `frequencies` - the frequencies at which the window is computed [c/d]
+///
+pub fn spectral_window(times: &[f64], frequencies: &[f64]) -> Vec<f64> {
+
+    assert!(!times.is_empty(), "spectral_window(): no observations");
+
+    return frequencies.iter().map(|frequency| {
+        let sum: Complex<f64> = times.iter().map(|time| Complex::from_polar(1.0, 2.0 * PI * frequency * time)).sum();
+        sum.norm() / times.len() as f64
+    }).collect();
+}
+
+
+
+
+
+
+
+
+
+/// Refines the frequencies, amplitudes and phases of a set of sinusoids plus a constant by fitting them
+/// simultaneously to a time series with the non-linear least-squares method of Levenberg-Marquardt.
+///
+/// # Arguments
+///
+/// * `times`     - times of the observations [d]
+/// * `values`    - the observed values
+/// * `sinusoids` - initial estimates, whose frequencies have to be accurate to a fraction of 1/T,
+///   with T the time base of the observations
+///
+/// Returns the fitted constant and sinusoids, with positive amplitudes and phases in (-pi, pi], or None if
+/// the sinusoids cannot be fitted simultaneously, e.g. because two frequencies coincide. The iterations stop
+/// at a singular step, which happens when an amplitude collapses to zero and its frequency becomes undefined.
+///
+pub fn refine_sinusoids(times: &[f64], values: &[f64], sinusoids: &[Sinusoid]) -> Option<(f64, Vec<Sinusoid>)> {
+
+    assert!(times.len() == values.len(), "refine_sinusoids(): times and values differ in length");
+    assert!(times.len() > 3 * sinusoids.len() + 1, "refine_sinusoids(): more parameters than observations");
+
+    // Start from the linear least-squares solution for the given frequencies, with the parameters
+    // (c, f_1, a_1, b_1, f_2, ...) of c + \sum_j a_j cos(2 pi f_j t) + b_j sin(2 pi f_j t)
+
+    let frequencies: Vec<f64> = sinusoids.iter().map(|sinusoid| sinusoid.frequency).collect();
+    let (constant, coefficients) = fit_sinusoids(times, values, &frequencies)?;
+    let mut parameters = vec![constant];
+    for (frequency, (a, b)) in frequencies.iter().zip(coefficients.iter()) {
+        parameters.extend([*frequency, *a, *b]);
+    }
+
+    let size = parameters.len();
+    let mut chi2 = sum_of_squared_residuals(times, values, &parameters);
+    let mut damping = 1.0e-3;
+    for _ in 0..200 {
+
+        // The normal equations J^T J delta = J^T r, with the diagonal enhanced by the damping factor
+
+        let mut matrix = vec![vec![0.0; size]; size];
+        let mut rhs = vec![0.0; size];
+        let mut derivatives = vec![0.0; size];
+        for (time, value) in times.iter().zip(values.iter()) {
+            derivatives[0] = 1.0;
+            for j in 0..(size - 1) / 3 {
+                let (frequency, a, b) = (parameters[3*j + 1], parameters[3*j + 2], parameters[3*j + 3]);
+                let (sine, cosine) = (2.0 * PI * frequency * time).sin_cos();
+                derivatives[3*j + 1] = 2.0 * PI * time * (b * cosine - a * sine);
+                derivatives[3*j + 2] = cosine;
+                derivatives[3*j + 3] = sine;
+            }
+            let residual = value - model_value(*time, &parameters);
+            for i in 0..size {
+                rhs[i] += derivatives[i] * residual;
+                for k in 0..size {
+                    matrix[i][k] += derivatives[i] * derivatives[k];
+                }
+            }
+        }
+        for (i, row) in matrix.iter_mut().enumerate() {
+            row[i] *= 1.0 + damping;
+        }
+
+        let Some(step) = solve_linear_system(matrix, rhs) else {
+            break;
+        };
+        let trial: Vec<f64> = parameters.iter().zip(step.iter()).map(|(p, dp)| p + dp).collect();
+        let trial_chi2 = sum_of_squared_residuals(times, values, &trial);
+
+        if trial_chi2 < chi2 {
+            let converged = chi2 - trial_chi2 <= 1.0e-12 * chi2;
+            parameters = trial;
+            chi2 = trial_chi2;
+            damping *= 0.1;
+            if converged {
+                break;
+            }
+        } else {
+            damping *= 10.0;
+            if damping > 1.0e10 {
+                break;
+            }
+        }
+    }
+
+    // a cos(x) + b sin(x) = A cos(x + phi) with A e^{i phi} = a - i b
+
+    let refined = (0..(size - 1) / 3).map(|j| {
+        let (amplitude, phase) = Complex::new(parameters[3*j + 2], -parameters[3*j + 3]).to_polar();
+        Sinusoid { frequency: parameters[3*j + 1], amplitude, phase }
+    }).collect();
+
+    return Some((parameters[0], refined));
+}
+
+
+
+
+
+
+
+
+
+/// The result of the iterative prewhitening
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct Prewhitening {
+    pub constant: f64,               // The fitted constant, in the units of the time series
+    pub sinusoids: Vec<Sinusoid>,    // The significant sinusoids, in the order of extraction
+    pub signal_to_noise: Vec<f64>,   // The S/N of each sinusoid in the final residuals
+    pub residuals: Vec<f64>,         // The observed values minus the constant and all sinusoids
+}
+
+
+
+
+
+
+
+
+
+/// Frequency analysis of an unevenly sampled time series: periodogram, spectral window and iterative
+/// prewhitening. In each step of the prewhitening the highest peak in the periodogram of the
+/// residuals is added, provided that it is resolved from the sinusoids found so far, and all sinusoids are
+/// refined simultaneously with refine_sinusoids().
+/// The prewhitening stops when the S/N of the new peak drops below the threshold, where the noise is the
+/// mean amplitude of the residuals in a box around the frequency (Breger et al. 1993, A&A 271, 482).
+///
+pub struct FrequencyAnalysis {
+    pub times: Vec<f64>,             // Times of the observations [d]
+    pub values: Vec<f64>,            // The observed values
+    pub min_frequency: f64,          // Lowest frequency of the periodogram [c/d]
+    pub max_frequency: f64,          // Highest frequency of the periodogram [c/d]
+    pub oversampling: f64,           // Number of frequencies per 1/T, with T the time base
+    pub snr_threshold: f64,          // Minimum S/N of a significant sinusoid
+    pub noise_box: f64,              // Width of the frequency box in which the noise is computed [c/d]
+    pub max_sinusoids: usize,        // Maximum number of sinusoids to extract
+}
+
+
+
+
+
+
+
+
+
+impl FrequencyAnalysis {
+
+    /// Creates a frequency analysis up to the pseudo-Nyquist frequency 1 / (2 median time step), with an
+    /// oversampling of 10, a S/N threshold of 4 in a box of 1 c/d, and at most 20 sinusoids
+    ///
+    /// # Arguments
+    ///
+    /// * `times`  - times of the observations, in increasing order [d]
+    /// * `values` - the observed values
+    ///
+    pub fn new(times: Vec<f64>, values: Vec<f64>) -> Self {
+
+        assert!(times.len() == values.len(), "FrequencyAnalysis::new(): times and values differ in length");
+        assert!(times.len() > 3, "FrequencyAnalysis::new(): need at least 4 observations");
+        assert!(times.windows(2).all(|w| w[0] < w[1]), "FrequencyAnalysis::new(): times not strictly increasing");
+
+        let mut steps: Vec<f64> = times.windows(2).map(|w| w[1] - w[0]).collect();
+        steps.sort_by(|a, b| a.total_cmp(b));
+        let max_frequency = 0.5 / steps[steps.len() / 2];
+
+        FrequencyAnalysis { times, values, min_frequency: 0.0, max_frequency, oversampling: 10.0, snr_threshold: 4.0,
+                            noise_box: 1.0, max_sinusoids: 20 }
+    }
+
+
+
+
+    /// Sets the frequency range of the periodogram [c/d]
+    ///
+    pub fn with_frequency_range(mut self, min_frequency: f64, max_frequency: f64) -> Self {
+        assert!(0.0 <= min_frequency && min_frequency < max_frequency, "FrequencyAnalysis::with_frequency_range(): invalid range");
+        self.min_frequency = min_frequency;
+        self.max_frequency = max_frequency;
+        self
+    }
+
+
+
+
+    /// Sets the number of frequencies per 1/T in the periodogram
+    ///
+    pub fn with_oversampling(mut self, oversampling: f64) -> Self {
+        self.oversampling = oversampling;
+        self
+    }
+
+
+
+
+    /// Sets the S/N threshold and the width of the box in which the noise is computed [c/d]
+    ///
+    pub fn with_snr_threshold(mut self, snr_threshold: f64, noise_box: f64) -> Self {
+        self.snr_threshold = snr_threshold;
+        self.noise_box = noise_box;
+        self
+    }
+
+
+
+
+    /// Sets the maximum number of sinusoids to extract
+    ///
+    pub fn with_max_sinusoids(mut self, max_sinusoids: usize) -> Self {
+        self.max_sinusoids = max_sinusoids;
+        self
+    }
+
+
+
+
+    /// Returns the frequency grid [c/d], with a step 1 / (oversampling T). The frequency 0 is skipped.
+    ///
+    pub fn frequencies(&self) -> Vec<f64> {
+        let time_base = self.times[self.times.len() - 1] - self.times[0];
+        let step = 1.0 / (self.oversampling * time_base);
+        let first = (self.min_frequency / step).ceil().max(1.0) as usize;
+        let last = (self.max_frequency / step).floor() as usize;
+        return (first..=last).map(|j| j as f64 * step).collect();
+    }
+
+
+
+
+    /// Computes the generalised Lomb-Scargle periodogram of the time series on the frequency grid
+    ///
+    pub fn periodogram(&self) -> Periodogram {
+        return lomb_scargle(&self.times, &self.values, &self.frequencies());
+    }
+
+
+
+
+    /// Computes the spectral window of the sampling on the frequency grid
+    ///
+    pub fn spectral_window(&self) -> Vec<f64> {
+        return spectral_window(&self.times, &self.frequencies());
+    }
+
+
+
+
+    /// Extracts the significant sinusoids by iterative prewhitening. The extraction stops when the new
+    /// sinusoid cannot be fitted together with the previous ones.
+    ///
+    pub fn prewhiten(&self) -> Prewhitening {
+
+        let frequencies = self.frequencies();
+        let resolution = 1.0 / (self.times[self.times.len() - 1] - self.times[0]);
+        let mean = self.values.iter().sum::<f64>() / self.values.len() as f64;
+        let mut result = Prewhitening { constant: mean, sinusoids: vec![], signal_to_noise: vec![],
+                                        residuals: self.values.iter().map(|value| value - mean).collect() };
+
+        while result.sinusoids.len() < self.max_sinusoids {
+
+            // Add the highest peak in the periodogram of the residuals, and refine all sinusoids. The power is
+            // used rather than the amplitude, which is poorly determined where the sinusoid is nearly degenerate
+            // with the constant, e.g. at frequencies below 1/T or at the daily alias of nightly observations.
+            // Frequencies within 1.5/T of an extracted one are not resolved (Loumos & Deeming 1978, Ap&SS 56, 285).
+
+            let periodogram = lomb_scargle(&self.times, &result.residuals, &frequencies);
+            let resolved = |j: &usize| result.sinusoids.iter().all(|sinusoid| (frequencies[*j] - sinusoid.frequency).abs() >= 1.5 * resolution);
+            let Some(peak) = (0..frequencies.len()).filter(resolved).max_by(|&i, &j| periodogram.power[i].total_cmp(&periodogram.power[j])) else {
+                break;
+            };
+            let mut sinusoids = result.sinusoids.clone();
+            sinusoids.push(Sinusoid { frequency: frequencies[peak], amplitude: periodogram.amplitudes[peak], phase: 0.0 });
+            let Some((constant, sinusoids)) = refine_sinusoids(&self.times, &self.values, &sinusoids) else {
+                break;
+            };
+            let residuals: Vec<f64> = self.times.iter().zip(self.values.iter())
+                                          .map(|(time, value)| value - constant - sinusoids.iter().map(|sinusoid| sinusoid.value(*time)).sum::<f64>())
+                                          .collect();
+
+            // The noise is computed after prewhitening, so that the window pattern of the peak itself
+            // does not count as noise
+
+            let periodogram = lomb_scargle(&self.times, &residuals, &frequencies);
+            let signal_to_noise: Vec<f64> = sinusoids.iter()
+                                                     .map(|sinusoid| self.signal_to_noise(&periodogram, sinusoid.frequency, sinusoid.amplitude))
+                                                     .collect();
+            if signal_to_noise[signal_to_noise.len() - 1] < self.snr_threshold {
+                break;
+            }
+
+            result = Prewhitening { constant, sinusoids, signal_to_noise, residuals };
+        }
+
+        return result;
+    }
+
+
+
+
+    /// Computes the ratio of `amplitude` to the mean amplitude of the periodogram of the residuals in the noise box
+    /// around `frequency`. Prewhitening a sinusoid also removes the noise within the resolution 1/T around its
+    /// frequency, so that this interval is left out.
+    ///
+    fn signal_to_noise(&self, periodogram: &Periodogram, frequency: f64, amplitude: f64) -> f64 {
+
+        let resolution = 1.0 / (self.times[self.times.len() - 1] - self.times[0]);
+        let (sum, count) = periodogram.frequencies.iter().zip(periodogram.amplitudes.iter())
+                                      .filter(|(f, _)| (*f - frequency).abs() <= 0.5 * self.noise_box && (*f - frequency).abs() >= resolution)
+                                      .fold((0.0, 0), |(sum, count), (_, a)| (sum + a, count + 1));
+
+        return if sum > 0.0 { amplitude * count as f64 / sum } else { f64::INFINITY };
+    }
+}
+
+
+
+
+
+
+
+
+
+/// Evaluates c + \sum_j a_j cos(2 pi f_j t) + b_j sin(2 pi f_j t) for the parameters (c, f_1, a_1, b_1, f_2, ...)
+///
+fn model_value(time: f64, parameters: &[f64]) -> f64 {
+    return parameters[0] + parameters[1..].chunks(3)
+                                          .map(|p| p[1] * (2.0 * PI * p[0] * time).cos() + p[2] * (2.0 * PI * p[0] * time).sin())
+                                          .sum::<f64>();
+}
+
+
+
+
+
+
+
+
+
+/// Computes the sum of the squared residuals of the model of model_value()
+///
+fn sum_of_squared_residuals(times: &[f64], values: &[f64], parameters: &[f64]) -> f64 {
+    return times.iter().zip(values.iter()).map(|(time, value)| (value - model_value(*time, parameters)).powi(2)).sum();
+}
+
+
+
+
+
+
+
+
+
+
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+
+    /// Unevenly sampled observations during 5 nights, of two sinusoids plus noise
+    ///
+    fn time_series() -> (Vec<f64>, Vec<f64>, [Sinusoid; 2]) {
+        let sinusoids = [Sinusoid { frequency: 6.71, amplitude: 5.0, phase: 0.9 }, Sinusoid { frequency: 9.03, amplitude: 2.0, phase: -2.2 }];
+        let times: Vec<f64> = (0..300).map(|n| f64::from(n / 60) + 0.005 * f64::from(n % 60) + 0.001 * f64::from(n).sin()).collect();
+
+        // Approximately Gaussian noise with sigma = 0.5 from the sum of 12 uniform deviates of a xorshift generator
+
+        let mut state: u64 = 88172645463325252;
+        let mut uniform = || { state ^= state << 13; state ^= state >> 7; state ^= state << 17; (state >> 11) as f64 / (1u64 << 53) as f64 };
+        let values = times.iter()
+                          .map(|t| 30.0 + sinusoids.iter().map(|s| s.value(*t)).sum::<f64>() + 0.5 * ((0..12).map(|_| uniform()).sum::<f64>() - 6.0))
+                          .collect();
+        return (times, values, sinusoids);
+    }
+
+    #[test]
+    fn test_periodogram_and_window() {
+        let (times, values, sinusoids) = time_series();
+        let analysis = FrequencyAnalysis::new(times, values).with_frequency_range(0.0, 20.0);
+        let periodogram = analysis.periodogram();
+        let peak = (0..periodogram.power.len()).max_by(|&i, &j| periodogram.power[i].total_cmp(&periodogram.power[j])).unwrap();
+        assert_approx_eq!(periodogram.frequencies[peak], sinusoids[0].frequency, 0.02);
+        assert!(periodogram.power[peak] > 0.5 && periodogram.power[peak] <= 1.0);
+
+        // The window of nightly observations has its first alias near 1 c/d
+
+        let window = spectral_window(&analysis.times, &[0.0, 0.5, 1.0]);
+        assert_approx_eq!(window[0], 1.0, 1.0e-14);
+        assert!(window[2] > window[1]);
+    }
+
+    #[test]
+    fn test_prewhitening_recovers_the_sinusoids() {
+        let (times, values, sinusoids) = time_series();
+        let result = FrequencyAnalysis::new(times, values).with_frequency_range(0.0, 20.0).prewhiten();
+        assert_eq!(result.sinusoids.len(), 2);
+        assert_approx_eq!(result.constant, 30.0, 0.05);
+        for (found, expected) in result.sinusoids.iter().zip(sinusoids.iter()) {
+            assert_approx_eq!(found.frequency, expected.frequency, 5.0e-3);
+            assert_approx_eq!(found.amplitude, expected.amplitude, 0.1);
+            assert_approx_eq!(found.phase, expected.phase, 0.1);
+        }
+        assert!(result.signal_to_noise.iter().all(|snr| *snr > 4.0));
+    }
+
+    #[test]
+    fn test_refine_degenerate_sinusoids() {
+        let (times, values, sinusoids) = time_series();
+
+        // Coinciding frequencies make the linear least-squares problem singular
+
+        assert!(refine_sinusoids(&times, &values, &[sinusoids[0], sinusoids[0]]).is_none());
+
+        // Without signal the amplitude collapses to zero and the frequency derivatives vanish
+
+        let constant = vec![30.0; times.len()];
+        let (fitted, refined) = refine_sinusoids(&times, &constant, &sinusoids[..1]).unwrap();
+        assert_approx_eq!(fitted, 30.0, 1.0e-10);
+        assert_approx_eq!(refined[0].amplitude, 0.0, 1.0e-10);
+    }
+}
@@ -0,0 +1,188 @@
+use num_complex::Complex;
+use crate::sphericalharmonics::*;
+use crate::wignerd::*;
+
+
+/// An expansion f(theta, phi) = \sum_{l=0}^{lmax} \sum_{m=-l}^{+l} a_lm Y_l^m(theta, phi) in the complex
+/// spherical harmonics of ylm_complex(), e.g. a pulsation pattern or a spot map. A real field has
+/// a_{l,-m} = (-1)^m conj(a_lm).
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct HarmonicExpansion {
+    lmax: u16,
+    coefficients: Vec<Complex<f64>>,           // a_lm at index l^2 + l + m
+}
+
+
+
+
+
+impl HarmonicExpansion {
+
+    /// Creates an expansion up to degree `lmax` with all coefficients zero
+    ///
+    pub fn new(lmax: u16) -> Self {
+        let size = (usize::from(lmax) + 1) * (usize::from(lmax) + 1);
+        return HarmonicExpansion { lmax, coefficients: vec![Complex::new(0.0, 0.0); size] };
+    }
+
+
+
+
+    /// Returns the maximum degree of the expansion
+    ///
+    pub fn lmax(&self) -> u16 {
+        return self.lmax;
+    }
+
+
+
+
+    /// Returns the coefficient a_lm, with 0 <= l <= lmax and -l <= m <= l
+    ///
+    pub fn coefficient(&self, l: u16, m: i16) -> Complex<f64> {
+        return self.coefficients[self.index(l, m)];
+    }
+
+
+
+
+    /// Sets the coefficient a_lm, with 0 <= l <= lmax and -l <= m <= l
+    ///
+    pub fn set_coefficient(&mut self, l: u16, m: i16, value: Complex<f64>) {
+        let index = self.index(l, m);
+        self.coefficients[index] = value;
+    }
+
+
+
+
+    /// Sets the coefficient a_lm and returns the expansion, for chaining
+    ///
+    pub fn with_coefficient(mut self, l: u16, m: i16, value: Complex<f64>) -> Self {
+        self.set_coefficient(l, m, value);
+        return self;
+    }
+
+
+
+
+    /// Evaluates the expansion in the point (theta, phi) [rad]
+    ///
+    pub fn evaluate(&self, theta: f64, phi: f64) -> Complex<f64> {
+        let mut sum = Complex::new(0.0, 0.0);
+        for l in 0..=self.lmax {
+            for m in -(l as i16)..=(l as i16) {
+                let coefficient = self.coefficient(l, m);
+                if coefficient != Complex::new(0.0, 0.0) {
+                    sum += coefficient * ylm_complex(l, m, theta, phi);
+                }
+            }
+        }
+        return sum;
+    }
+
+
+
+
+    /// Rotates the field over the Euler angles (alpha, beta, gamma) in the z-y-z convention, i.e.
+    /// with R = R_z(alpha) R_y(beta) R_z(gamma) the rotated field is g(r) = f(R^{-1} r). Equivalently,
+    /// the result contains the coefficients of the unrotated field f in a frame whose axes are
+    /// rotated by R^{-1}. Since Y_l^m(R^{-1} r) = \sum_k D^{(l)}_{km}(alpha, beta, gamma) Y_l^k(r),
+    /// the rotated coefficients are
+    ///     b_lk = \sum_m D^{(l)}_{km}(alpha, beta, gamma) a_lm
+    /// and each degree l is rotated independently.
+    ///
+    /// # Arguments
+    ///
+    /// * `alpha` - first Euler angle, around the z-axis [rad]
+    /// * `beta`  - second Euler angle, around the y-axis [rad]
+    /// * `gamma` - third Euler angle, around the z-axis [rad]
+    ///
+    pub fn rotate(&self, alpha: f64, beta: f64, gamma: f64) -> Self {
+
+        let mut rotated = HarmonicExpansion::new(self.lmax);
+        for wigner in WignerD::up_to(self.lmax, beta) {
+            let l = wigner.l();
+            let size = l as i16;
+            for k in -size..=size {
+                let value: Complex<f64> = (-size..=size).map(|m| wigner.big_dkm(k, m, alpha, gamma) * self.coefficient(l, m)).sum();
+                rotated.set_coefficient(l, k, value);
+            }
+        }
+
+        return rotated;
+    }
+
+
+
+
+    /// Returns the position of a_lm in the coefficient vector
+    ///
+    fn index(&self, l: u16, m: i16) -> usize {
+        assert!(l <= self.lmax && m.unsigned_abs() <= l, "HarmonicExpansion: need l <= lmax and |m| <= l");
+        return (usize::from(l) * usize::from(l) + usize::from(l)).wrapping_add_signed(isize::from(m));
+    }
+}
+
+
+
+
+
+
+
+
+
+
+
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+
+    /// Computes the spherical coordinates of the point R^{-1} r with R = R_z(alpha) R_y(beta) R_z(gamma)
+    ///
+    fn inverse_rotation(theta: f64, phi: f64, alpha: f64, beta: f64, gamma: f64) -> (f64, f64) {
+        let rotate_z = |v: [f64; 3], angle: f64| [angle.cos() * v[0] - angle.sin() * v[1], angle.sin() * v[0] + angle.cos() * v[1], v[2]];
+        let rotate_y = |v: [f64; 3], angle: f64| [angle.cos() * v[0] + angle.sin() * v[2], v[1], -angle.sin() * v[0] + angle.cos() * v[2]];
+        let point = [theta.sin() * phi.cos(), theta.sin() * phi.sin(), theta.cos()];
+        let point = rotate_z(rotate_y(rotate_z(point, -alpha), -beta), -gamma);
+        return (point[2].clamp(-1.0, 1.0).acos(), point[1].atan2(point[0]));
+    }
+
+    #[test]
+    fn test_rotation_equals_pointwise_evaluation() {
+
+        let expansion = HarmonicExpansion::new(4).with_coefficient(0, 0, Complex::new(0.5, 0.0))
+                                                 .with_coefficient(1, -1, Complex::new(0.3, -0.2))
+                                                 .with_coefficient(2, 1, Complex::new(-0.7, 0.4))
+                                                 .with_coefficient(3, 3, Complex::new(0.1, 0.9))
+                                                 .with_coefficient(4, -2, Complex::new(1.2, 0.0))
+                                                 .with_coefficient(4, 0, Complex::new(0.0, -0.6));
+        let (alpha, beta, gamma) = (0.4, 1.3, -2.1);
+        let rotated = expansion.rotate(alpha, beta, gamma);
+
+        for (theta, phi) in [(0.2, 0.1), (1.0, 2.0), (2.5, -1.2), (1.6, 4.0)] {
+
+            // Evaluate the unrotated field in R^{-1} r with ylmnorm() and plmcos()
+
+            let (theta0, phi0) = inverse_rotation(theta, phi, alpha, beta, gamma);
+            let (sintheta0, costheta0) = (theta0.sin(), theta0.cos());
+            let mut expected = Complex::new(0.0, 0.0);
+            for l in 0..=4_u16 {
+                for m in -(l as i16)..=(l as i16) {
+                    let ylm = ylmnorm(l, m) * plmcos(l, m.unsigned_abs(), sintheta0, costheta0) * Complex::from_polar(1.0, f64::from(m) * phi0);
+                    expected += expansion.coefficient(l, m) * ylm;
+                }
+            }
+
+            let computed = rotated.evaluate(theta, phi);
+            assert_approx_eq!(computed.re, expected.re, 1.0e-8);
+            assert_approx_eq!(computed.im, expected.im, 1.0e-8);
+        }
+    }
+}
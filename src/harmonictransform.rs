@@ -0,0 +1,232 @@
+use std::f64::consts::PI;
+use num_complex::Complex;
+use crate::auxilliary::*;
+use crate::sphericalharmonics::*;
+use crate::harmonicexpansion::*;
+
+
+/// A spherical harmonic transform between the coefficients a_lm (0 <= l <= lmax) of a HarmonicExpansion
+/// and the values of the field on a grid of n_theta colatitudes times n_phi equidistant azimuths
+/// phi_k = 2 pi k / n_phi. The analysis computes
+///     a_lm = \int f(theta, phi) conj(Y_l^m(theta, phi)) dOmega
+/// with a quadrature in cos(theta) and the trapezoidal rule in phi. For a field band-limited to lmax
+/// the integrand is a polynomial of degree 2 lmax in cos(theta) and a trigonometric polynomial of degree
+/// 2 lmax in phi, so that with n_phi = 2 lmax + 1 both synthesis and analysis are exact to round-off on
+/// the Gauss-Legendre grid, with the n_theta = lmax + 1 Gauss-Legendre nodes in cos(theta), and on the
+/// equiangular grid, with the n_theta = 2 lmax + 2 colatitudes theta_j = pi (j + 1/2) / n_theta and the
+/// weights of Fejer's first rule, which is exact for polynomials of degree n_theta - 1.
+///
+/// The Legendre functions are the normalised plmbar() ones, so that high degrees can be used.
+///
+#[derive(Clone, Debug)]
+pub struct HarmonicTransform {
+    lmax: u16,
+    thetas: Vec<f64>,                // colatitudes, increasing [rad]
+    weights: Vec<f64>,               // quadrature weights for \int ... d(cos(theta))
+    n_phi: usize,
+    legendre: Vec<Vec<Vec<f64>>>,    // legendre[j][m][l-m] = Pbar_l^m(cos(theta_j))
+}
+
+
+
+
+
+impl HarmonicTransform {
+
+    /// Creates the transform on the Gauss-Legendre grid with lmax + 1 colatitudes and 2 lmax + 1 azimuths
+    ///
+    pub fn gauss_legendre(lmax: u16) -> Self {
+
+        // The abscissas in cos(theta) are increasing, so reverse them to obtain increasing colatitudes
+
+        let (abscissas, weights) = gauss_legendre(usize::from(lmax) + 1);
+        let thetas = abscissas.iter().rev().map(|x| x.acos()).collect();
+        let weights = weights.into_iter().rev().collect();
+
+        return Self::with_grid(lmax, thetas, weights);
+    }
+
+
+
+
+    /// Creates the transform on the equiangular grid with 2 lmax + 2 colatitudes and 2 lmax + 1 azimuths
+    ///
+    pub fn equiangular(lmax: u16) -> Self {
+
+        // Fejer's first rule: w_j = 2/n (1 - 2 \sum_{k=1}^{n/2} cos(2 k theta_j) / (4 k^2 - 1))
+
+        let n_theta = 2 * usize::from(lmax) + 2;
+        let thetas: Vec<f64> = (0..n_theta).map(|j| PI * (j as f64 + 0.5) / n_theta as f64).collect();
+        let weights = thetas.iter().map(|theta| {
+            let sum: f64 = (1..=n_theta/2).map(|k| (2.0 * k as f64 * theta).cos() / (4.0 * (k * k) as f64 - 1.0)).sum();
+            2.0 / n_theta as f64 * (1.0 - 2.0 * sum)
+        }).collect();
+
+        return Self::with_grid(lmax, thetas, weights);
+    }
+
+
+
+
+    /// Precomputes the Legendre functions for the given colatitudes
+    ///
+    fn with_grid(lmax: u16, thetas: Vec<f64>, weights: Vec<f64>) -> Self {
+        let legendre = thetas.iter().map(|theta| {
+            let (sintheta, costheta) = theta.sin_cos();
+            (0..=lmax).map(|m| plmbar_column(lmax, m, sintheta, costheta)).collect()
+        }).collect();
+        let n_phi = 2 * usize::from(lmax) + 1;
+        return HarmonicTransform { lmax, thetas, weights, n_phi, legendre };
+    }
+
+
+
+
+    /// Returns the maximum degree of the transform
+    ///
+    pub fn lmax(&self) -> u16 {
+        return self.lmax;
+    }
+
+
+
+
+    /// Returns the colatitudes of the grid [rad]
+    ///
+    pub fn thetas(&self) -> &[f64] {
+        return &self.thetas;
+    }
+
+
+
+
+    /// Returns the azimuths of the grid [rad]
+    ///
+    pub fn phis(&self) -> Vec<f64> {
+        return (0..self.n_phi).map(|k| 2.0 * PI * k as f64 / self.n_phi as f64).collect();
+    }
+
+
+
+
+    /// Computes the values of the expansion on the grid, in the order f[j * n_phi + k] = f(theta_j, phi_k).
+    /// Coefficients with l > lmax of the transform are ignored.
+    ///
+    pub fn synthesis(&self, expansion: &HarmonicExpansion) -> Vec<Complex<f64>> {
+
+        let lmax = self.lmax.min(expansion.lmax());
+        let phis = self.phis();
+        let mut values = vec![Complex::new(0.0, 0.0); self.thetas.len() * self.n_phi];
+
+        for (j, legendre) in self.legendre.iter().enumerate() {
+
+            // First sum over l for each m, then over m for each azimuth
+
+            let mut fourier = Vec::with_capacity(2 * usize::from(lmax) + 1);
+            for m in -(lmax as i16)..=(lmax as i16) {
+                let column = &legendre[usize::from(m.unsigned_abs())];
+                let sign = if m > 0 && m % 2 == 1 { -1.0 } else { 1.0 };
+                let sum: Complex<f64> = (m.unsigned_abs()..=lmax).map(|l| expansion.coefficient(l, m) * column[usize::from(l - m.unsigned_abs())]).sum();
+                fourier.push((m, sign * sum));
+            }
+
+            for (k, phi) in phis.iter().enumerate() {
+                values[j * self.n_phi + k] = fourier.iter().map(|(m, coefficient)| coefficient * Complex::from_polar(1.0, f64::from(*m) * phi)).sum();
+            }
+        }
+
+        return values;
+    }
+
+
+
+
+    /// Computes the coefficients a_lm, 0 <= l <= lmax, of a field given on the grid in the order of synthesis()
+    ///
+    pub fn analysis(&self, values: &[Complex<f64>]) -> HarmonicExpansion {
+
+        assert!(values.len() == self.thetas.len() * self.n_phi, "HarmonicTransform::analysis(): values do not match the grid");
+
+        let lmax = self.lmax;
+        let phis = self.phis();
+        let mut expansion = HarmonicExpansion::new(lmax);
+
+        for (j, legendre) in self.legendre.iter().enumerate() {
+            let row = &values[j * self.n_phi..(j + 1) * self.n_phi];
+            for m in -(lmax as i16)..=(lmax as i16) {
+
+                // The Fourier coefficient of order m of this ring, times the quadrature weights
+
+                let fourier: Complex<f64> = row.iter().zip(phis.iter())
+                                               .map(|(value, phi)| value * Complex::from_polar(1.0, -f64::from(m) * phi))
+                                               .sum();
+                let fourier = fourier * 2.0 * PI / self.n_phi as f64 * self.weights[j];
+
+                let column = &legendre[usize::from(m.unsigned_abs())];
+                let sign = if m > 0 && m % 2 == 1 { -1.0 } else { 1.0 };
+                for l in m.unsigned_abs()..=lmax {
+                    let coefficient = expansion.coefficient(l, m) + sign * column[usize::from(l - m.unsigned_abs())] * fourier;
+                    expansion.set_coefficient(l, m, coefficient);
+                }
+            }
+        }
+
+        return expansion;
+    }
+}
+
+
+
+
+
+
+
+
+
+
+
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn test_transforms_are_exact() {
+
+        // A band-limited field with pseudo-random coefficients
+
+        let lmax = 12_u16;
+        let mut expansion = HarmonicExpansion::new(lmax);
+        for l in 0..=lmax {
+            for m in -(l as i16)..=(l as i16) {
+                let seed = f64::from(7 * l) + f64::from(m) * 1.3;
+                expansion.set_coefficient(l, m, Complex::new(seed.sin(), (2.0 * seed).cos()));
+            }
+        }
+
+        for transform in [HarmonicTransform::gauss_legendre(lmax), HarmonicTransform::equiangular(lmax)] {
+
+            // The synthesis agrees with the direct evaluation of the expansion
+
+            let values = transform.synthesis(&expansion);
+            let phis = transform.phis();
+            for (j, k) in [(0, 0), (3, 5), (transform.thetas().len() - 1, 7)] {
+                let expected = expansion.evaluate(transform.thetas()[j], phis[k]);
+                assert_approx_eq!((values[j * phis.len() + k] - expected).norm(), 0.0, 1.0e-11);
+            }
+
+            // The analysis recovers the coefficients
+
+            let recovered = transform.analysis(&values);
+            for l in 0..=lmax {
+                for m in -(l as i16)..=(l as i16) {
+                    assert_approx_eq!((recovered.coefficient(l, m) - expansion.coefficient(l, m)).norm(), 0.0, 1.0e-12);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,500 @@
+use std::f64::consts::PI;
+use std::sync::OnceLock;
+use num_complex::Complex;
+use crate::auxilliary::*;
+
+
+// All intrinsic profiles return the absorption 1 - F/F_c of the local line as a function of the
+// velocity offset dv = v - v_los [km/s] from the Doppler-shifted line centre. The width and the
+// depth can depend on the local effective temperature Teff [K] and gravity log g [cgs].
+
+
+
+
+
+/// Power-law dependence of a profile parameter on the local atmosphere:
+///     q(Teff, log g) = q_0 * (Teff / teff_ref)^alpha * 10^(beta * (log g - logg_ref))
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AtmosphereScaling {
+    pub teff_ref: f64,               // Reference effective temperature [K]
+    pub logg_ref: f64,               // Reference gravity [log cgs]
+    pub alpha: f64,                  // d ln q / d ln Teff
+    pub beta: f64,                   // d log q / d log g
+}
+
+
+
+
+impl AtmosphereScaling {
+
+    /// Returns the scaling factor q(Teff, log g) / q_0
+    ///
+    pub fn factor(&self, teff: f64, logg: f64) -> f64 {
+
+        if self.alpha == 0.0 && self.beta == 0.0 {
+            return 1.0;
+        }
+
+        return (teff / self.teff_ref).powf(self.alpha) * 10.0_f64.powf(self.beta * (logg - self.logg_ref));
+    }
+}
+
+
+
+
+impl Default for AtmosphereScaling {
+
+    /// No dependence on the atmosphere at all
+    ///
+    fn default() -> Self {
+        AtmosphereScaling { teff_ref: 5772.0, logg_ref: 4.438, alpha: 0.0, beta: 0.0 }
+    }
+}
+
+
+
+
+
+
+
+
+
+/// A local (intrinsic) line profile
+///
+pub trait IntrinsicProfile {
+
+    /// Returns the absorption 1 - F/F_c at a velocity offset `dv` [km/s] from the line centre,
+    /// for a surface element with effective temperature `teff` [K] and gravity `logg` [cgs].
+    ///
+    fn absorption(&self, dv: f64, teff: f64, logg: f64) -> f64;
+
+    /// Returns the absorption at the wavelength `wavelength` of a line with rest wavelength
+    /// `rest_wavelength` (same units), emitted by an element moving with radial velocity `v_los` [km/s].
+    ///
+    fn absorption_at_wavelength(&self, wavelength: f64, rest_wavelength: f64, v_los: f64, teff: f64, logg: f64) -> f64 {
+        let dv = SPEED_OF_LIGHT_KM_S * (wavelength - rest_wavelength) / rest_wavelength - v_los;
+        return self.absorption(dv, teff, logg);
+    }
+}
+
+
+
+
+
+
+
+
+
+/// Gaussian profile: A(dv) = depth * exp(-dv^2 / (2 sigma^2))
+///
+/// The width consists of a fixed part and, optionally, a thermal part k Teff / m of an atom with mass m.
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GaussianProfile {
+    pub sigma: f64,                  // Non-thermal standard deviation [km/s]
+    pub atomic_mass: Option<f64>,    // Mass of the absorbing atom [amu], None to ignore thermal broadening
+    pub depth: f64,                  // Central depth, 0 <= depth <= 1
+    pub depth_scaling: AtmosphereScaling,
+}
+
+
+
+
+impl GaussianProfile {
+
+    /// Creates a Gaussian profile with a fixed standard deviation `sigma` [km/s] and central depth `depth`
+    ///
+    pub fn new(sigma: f64, depth: f64) -> Self {
+        GaussianProfile { sigma, atomic_mass: None, depth, depth_scaling: AtmosphereScaling::default() }
+    }
+
+    /// Creates a Gaussian profile broadened by the thermal motion of an atom of mass `atomic_mass` [amu]
+    /// and by a microturbulent velocity `microturbulence` [km/s] (most probable velocity).
+    ///
+    pub fn thermal(atomic_mass: f64, microturbulence: f64, depth: f64) -> Self {
+        GaussianProfile { sigma: microturbulence / 2.0_f64.sqrt(), atomic_mass: Some(atomic_mass), depth,
+                          depth_scaling: AtmosphereScaling::default() }
+    }
+
+    /// Lets the central depth vary with the local atmosphere
+    ///
+    pub fn with_depth_scaling(mut self, scaling: AtmosphereScaling) -> Self {
+        self.depth_scaling = scaling;
+        self
+    }
+
+    /// Returns the standard deviation [km/s] for a local effective temperature `teff` [K]
+    ///
+    pub fn width(&self, teff: f64) -> f64 {
+        match self.atomic_mass {
+            Some(mass) => (self.sigma * self.sigma + BOLTZMANN_OVER_AMU * teff / mass).sqrt(),
+            None => self.sigma,
+        }
+    }
+}
+
+
+
+
+impl IntrinsicProfile for GaussianProfile {
+
+    fn absorption(&self, dv: f64, teff: f64, logg: f64) -> f64 {
+        let sigma = self.width(teff);
+        return self.depth * self.depth_scaling.factor(teff, logg) * (-0.5 * dv * dv / (sigma * sigma)).exp();
+    }
+}
+
+
+
+
+
+
+
+
+
+/// Lorentzian profile: A(dv) = depth * gamma^2 / (dv^2 + gamma^2)
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LorentzianProfile {
+    pub gamma: f64,                  // Half width at half maximum [km/s]
+    pub depth: f64,                  // Central depth, 0 <= depth <= 1
+    pub gamma_scaling: AtmosphereScaling,
+    pub depth_scaling: AtmosphereScaling,
+}
+
+
+
+
+impl LorentzianProfile {
+
+    /// Creates a Lorentzian profile with half width at half maximum `gamma` [km/s] and central depth `depth`
+    ///
+    pub fn new(gamma: f64, depth: f64) -> Self {
+        LorentzianProfile { gamma, depth, gamma_scaling: AtmosphereScaling::default(), depth_scaling: AtmosphereScaling::default() }
+    }
+
+    /// Lets the damping width vary with the local atmosphere, e.g. with log g for pressure broadening
+    ///
+    pub fn with_gamma_scaling(mut self, scaling: AtmosphereScaling) -> Self {
+        self.gamma_scaling = scaling;
+        self
+    }
+
+    /// Lets the central depth vary with the local atmosphere
+    ///
+    pub fn with_depth_scaling(mut self, scaling: AtmosphereScaling) -> Self {
+        self.depth_scaling = scaling;
+        self
+    }
+}
+
+
+
+
+impl IntrinsicProfile for LorentzianProfile {
+
+    fn absorption(&self, dv: f64, teff: f64, logg: f64) -> f64 {
+        let gamma = self.gamma * self.gamma_scaling.factor(teff, logg);
+        return self.depth * self.depth_scaling.factor(teff, logg) * gamma * gamma / (dv * dv + gamma * gamma);
+    }
+}
+
+
+
+
+
+
+
+
+
+/// Voigt profile, i.e. the convolution of a Gaussian and a Lorentzian, scaled to a given central depth:
+///     A(dv) = depth * Re w(z) / Re w(z_0),   z = (dv + i gamma) / (sigma sqrt(2)),   z_0 = i gamma / (sigma sqrt(2))
+/// with w(z) the Faddeeva function.
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct VoigtProfile {
+    pub gaussian: GaussianProfile,   // Gaussian component, its depth is ignored
+    pub gamma: f64,                  // Half width at half maximum of the Lorentzian component [km/s]
+    pub depth: f64,                  // Central depth, 0 <= depth <= 1
+    pub gamma_scaling: AtmosphereScaling,
+    pub depth_scaling: AtmosphereScaling,
+}
+
+
+
+
+impl VoigtProfile {
+
+    /// Creates a Voigt profile from a Gaussian component (whose depth is ignored), the Lorentzian
+    /// half width at half maximum `gamma` [km/s], and the central depth `depth`.
+    ///
+    pub fn new(gaussian: GaussianProfile, gamma: f64, depth: f64) -> Self {
+        VoigtProfile { gaussian, gamma, depth, gamma_scaling: AtmosphereScaling::default(), depth_scaling: AtmosphereScaling::default() }
+    }
+
+    /// Lets the damping width vary with the local atmosphere
+    ///
+    pub fn with_gamma_scaling(mut self, scaling: AtmosphereScaling) -> Self {
+        self.gamma_scaling = scaling;
+        self
+    }
+
+    /// Lets the central depth vary with the local atmosphere
+    ///
+    pub fn with_depth_scaling(mut self, scaling: AtmosphereScaling) -> Self {
+        self.depth_scaling = scaling;
+        self
+    }
+}
+
+
+
+
+impl IntrinsicProfile for VoigtProfile {
+
+    fn absorption(&self, dv: f64, teff: f64, logg: f64) -> f64 {
+
+        let scale = 1.0 / (self.gaussian.width(teff) * 2.0_f64.sqrt());
+        let gamma = self.gamma * self.gamma_scaling.factor(teff, logg);
+        let centre = faddeeva(Complex::new(0.0, gamma * scale)).re;
+        let value = faddeeva(Complex::new(dv * scale, gamma * scale)).re;
+
+        return self.depth * self.depth_scaling.factor(teff, logg) * value / centre;
+    }
+}
+
+
+
+
+
+
+
+
+
+/// Tabulated profile, linearly interpolated in velocity and zero outside the table
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct TabulatedProfile {
+    velocity: Vec<f64>,
+    absorption: Vec<f64>,
+    pub depth_scaling: AtmosphereScaling,
+}
+
+
+
+
+impl TabulatedProfile {
+
+    /// Creates a tabulated profile
+    ///
+    /// # Arguments
+    ///
+    /// * `velocity`   - strictly increasing velocity offsets from the line centre [km/s]
+    /// * `absorption` - the corresponding absorption 1 - F/F_c
+    ///
+    pub fn new(velocity: Vec<f64>, absorption: Vec<f64>) -> Self {
+
+        assert!(velocity.len() >= 2, "TabulatedProfile::new(): need at least 2 points");
+        assert!(velocity.len() == absorption.len(), "TabulatedProfile::new(): velocity and absorption differ in length");
+        assert!(velocity.windows(2).all(|w| w[0] < w[1]), "TabulatedProfile::new(): velocity not strictly increasing");
+
+        TabulatedProfile { velocity, absorption, depth_scaling: AtmosphereScaling::default() }
+    }
+
+    /// Lets the depth vary with the local atmosphere
+    ///
+    pub fn with_depth_scaling(mut self, scaling: AtmosphereScaling) -> Self {
+        self.depth_scaling = scaling;
+        self
+    }
+}
+
+
+
+
+impl IntrinsicProfile for TabulatedProfile {
+
+    fn absorption(&self, dv: f64, teff: f64, logg: f64) -> f64 {
+
+        let last = self.velocity.len() - 1;
+        if dv < self.velocity[0] || dv > self.velocity[last] {
+            return 0.0;
+        }
+
+        let j = (self.velocity.partition_point(|v| *v <= dv) - 1).min(last - 1);
+        let fraction = (dv - self.velocity[j]) / (self.velocity[j+1] - self.velocity[j]);
+        let absorption = self.absorption[j] + fraction * (self.absorption[j+1] - self.absorption[j]);
+
+        return absorption * self.depth_scaling.factor(teff, logg);
+    }
+}
+
+
+
+
+
+
+
+
+
+/// Computes the Faddeeva function w(z) = e^{-z^2} erfc(-i z) for Im(z) >= 0
+///
+/// This uses the rational approximation of Weideman (1994, SIAM J. Numer. Anal. 31, 1497)
+/// with N = 32 terms, which is accurate to about 1e-13 in the upper half plane.
+///
+pub fn faddeeva(z: Complex<f64>) -> Complex<f64> {
+
+    const N: usize = 32;
+
+    let coefficients = weideman_coefficients();
+    let big_l = (N as f64 / 2.0_f64.sqrt()).sqrt();
+    let i = Complex::new(0.0, 1.0);
+
+    let denominator = Complex::new(big_l, 0.0) - i * z;
+    let zeta = (Complex::new(big_l, 0.0) + i * z) / denominator;
+
+    // Horner evaluation of \sum_{n=1}^N a_n zeta^{n-1}
+
+    let mut polynomial = Complex::new(0.0, 0.0);
+    for a_n in coefficients.iter().rev() {
+        polynomial = polynomial * zeta + a_n;
+    }
+
+    return 2.0 * polynomial / (denominator * denominator) + (1.0 / PI.sqrt()) / denominator;
+}
+
+
+
+
+
+
+
+
+
+/// Returns the coefficients a_1..a_N of Weideman's rational approximation, computed once with a
+/// discrete cosine transform of f(t) = exp(-t^2) (L^2 + t^2) sampled at t = L tan(k pi / (2M)).
+///
+fn weideman_coefficients() -> &'static [f64] {
+
+    static COEFFICIENTS: OnceLock<Vec<f64>> = OnceLock::new();
+
+    COEFFICIENTS.get_or_init(|| {
+        const N: usize = 32;
+        let big_m = 2 * N as i64;
+        let big_l = (N as f64 / 2.0_f64.sqrt()).sqrt();
+
+        let samples: Vec<(f64, f64)> = (-big_m+1..big_m).map(|k| {
+            let t = big_l * (k as f64 * PI / (2 * big_m) as f64).tan();
+            (k as f64, (-t * t).exp() * (big_l * big_l + t * t))
+        }).collect();
+
+        (1..=N).map(|n| {
+            let sum: f64 = samples.iter().map(|(k, f)| f * (PI * k * n as f64 / big_m as f64).cos()).sum();
+            sum / (2 * big_m) as f64
+        }).collect()
+    })
+}
+
+
+
+
+
+
+
+
+
+
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn test_faddeeva() {
+
+        // On the real axis Re w(x) = exp(-x^2), and w(i y) = exp(y^2) erfc(y)
+
+        for x in [0.0, 0.3, 1.2, 2.5] {
+            assert_approx_eq!(faddeeva(Complex::new(x, 0.0)).re, f64::exp(-x * x), 1.0e-12);
+        }
+        assert_approx_eq!(faddeeva(Complex::new(0.0, 1.0)).re, 0.427583576155807, 1.0e-12);
+        assert_approx_eq!(faddeeva(Complex::new(0.0, 1.0)).im, 0.0, 1.0e-12);
+    }
+
+    #[test]
+    fn test_voigt_limits() {
+
+        // Without damping a Voigt profile reduces to its Gaussian component
+
+        let gaussian = GaussianProfile::thermal(56.0, 2.0, 0.7);
+        let voigt = VoigtProfile::new(gaussian, 0.0, 0.7);
+        for dv in [-6.0, -1.0, 0.0, 2.5] {
+            assert_approx_eq!(voigt.absorption(dv, 15000.0, 4.0), gaussian.absorption(dv, 15000.0, 4.0), 1.0e-10);
+        }
+
+        // A Voigt profile with a vanishing Gaussian width reduces to its Lorentzian component
+
+        let voigt = VoigtProfile::new(GaussianProfile::new(1.0e-3, 1.0), 2.0, 0.7);
+        let lorentzian = LorentzianProfile::new(2.0, 0.7);
+        for dv in [-6.0, -1.0, 0.0, 2.5] {
+            assert_approx_eq!(voigt.absorption(dv, 15000.0, 4.0), lorentzian.absorption(dv, 15000.0, 4.0), 1.0e-6);
+        }
+    }
+
+    #[test]
+    fn test_gaussian_and_lorentzian_profiles() {
+
+        // The thermal width of iron at 15000 K with a microturbulence of 2 km/s, sigma^2 = xi^2 / 2 + k T / m
+
+        let gaussian = GaussianProfile::thermal(56.0, 2.0, 0.7);
+        assert_approx_eq!(gaussian.width(15000.0).powi(2), 2.0 + 8.314462618e-3 * 15000.0 / 56.0, 1.0e-12);
+        assert_approx_eq!(gaussian.width(0.0), 2.0_f64.sqrt(), 1.0e-12);
+        assert_approx_eq!(gaussian.absorption(gaussian.width(15000.0), 15000.0, 4.0), 0.7 * (-0.5_f64).exp(), 1.0e-12);
+
+        // The Lorentzian has half its central depth at +/- gamma, and gamma proportional to the gravity
+        // gives half the width at log g = 3.7
+
+        let lorentzian = LorentzianProfile::new(3.0, 0.6);
+        for dv in [-3.0, 3.0] {
+            assert_approx_eq!(lorentzian.absorption(dv, 15000.0, 4.0), 0.3, 1.0e-12);
+        }
+        let scaling = AtmosphereScaling { teff_ref: 15000.0, logg_ref: 4.0, alpha: 0.0, beta: 1.0 };
+        let pressure_broadened = lorentzian.with_gamma_scaling(scaling);
+        let logg = 4.0 - 2.0_f64.log10();
+        assert_approx_eq!(pressure_broadened.absorption(1.5, 15000.0, logg), 0.3, 1.0e-12);
+        assert_approx_eq!(pressure_broadened.absorption(3.0, 15000.0, 4.0), 0.3, 1.0e-12);
+    }
+
+    #[test]
+    fn test_atmosphere_scaling_and_tabulated_profile() {
+
+        // q = q_0 (Teff / teff_ref)^alpha 10^(beta (log g - logg_ref))
+
+        let scaling = AtmosphereScaling { teff_ref: 10000.0, logg_ref: 4.0, alpha: 1.5, beta: -0.4 };
+        assert_approx_eq!(scaling.factor(10000.0, 4.0), 1.0, 1.0e-14);
+        assert_approx_eq!(scaling.factor(20000.0, 4.5), 2.0_f64.powf(1.5) * 10.0_f64.powf(-0.2), 1.0e-12);
+        assert_eq!(AtmosphereScaling::default().factor(20000.0, 3.0), 1.0);
+
+        let gaussian = GaussianProfile::new(5.0, 0.4).with_depth_scaling(scaling);
+        assert_approx_eq!(gaussian.absorption(0.0, 20000.0, 4.5), 0.4 * scaling.factor(20000.0, 4.5), 1.0e-12);
+
+        // Linear interpolation inside the table, the tabulated values at both ends, and zero outside
+
+        let table = TabulatedProfile::new(vec![-2.0, 0.0, 1.0, 3.0], vec![0.1, 0.5, 0.3, 0.0]);
+        assert_approx_eq!(table.absorption(-1.0, 10000.0, 4.0), 0.3, 1.0e-14);
+        assert_approx_eq!(table.absorption(0.5, 10000.0, 4.0), 0.4, 1.0e-14);
+        assert_approx_eq!(table.absorption(2.9, 10000.0, 4.0), 0.015, 1.0e-14);
+        assert_approx_eq!(table.absorption(-2.0, 10000.0, 4.0), 0.1, 1.0e-14);
+        assert_approx_eq!(table.absorption(3.0, 10000.0, 4.0), 0.0, 1.0e-14);
+        assert_eq!(table.absorption(-2.001, 10000.0, 4.0), 0.0);
+        assert_eq!(table.absorption(3.001, 10000.0, 4.0), 0.0);
+
+        let scaled = table.with_depth_scaling(scaling);
+        assert_approx_eq!(scaled.absorption(0.0, 20000.0, 4.5), 0.5 * scaling.factor(20000.0, 4.5), 1.0e-12);
+    }
+}
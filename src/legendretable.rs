@@ -0,0 +1,185 @@
+/// A table of the associated Legendre functions P_l^m(cos(theta)) of plmcos(), together with their
+/// first and second derivatives with respect to theta, for all 0 <= m <= l <= lmax at a single theta.
+/// The whole table is computed in one pass, so that it can be reused for all modes in a surface point.
+///
+/// The functions follow the recurrences
+///     P_m^m     = (2m - 1) sin(theta) P_{m-1}^{m-1}
+///     P_{m+1}^m = (2m + 1) cos(theta) P_m^m
+///     (l - m) P_l^m = (2l - 1) cos(theta) P_{l-1}^m - (l + m - 1) P_{l-2}^m
+/// and the derivatives the ladder relations of deriv1_plmcos_dtheta_polesafe(), so that none of them
+/// divides by sin(theta) and the table is also valid at the poles.
+///
+#[derive(Clone, Debug)]
+pub struct LegendreTable {
+    lmax: u16,
+    plm: Vec<f64>,                   // P_l^m at index l(l+1)/2 + m
+    deriv1: Vec<f64>,                // dP_l^m/dtheta
+    deriv2: Vec<f64>,                // d^2P_l^m/dtheta^2
+}
+
+
+
+
+
+impl LegendreTable {
+
+    /// Computes the table for all degrees up to `lmax`
+    ///
+    /// # Arguments
+    ///
+    /// * `lmax`     - the maximum degree
+    /// * `sintheta` - sin(theta)
+    /// * `costheta` - cos(theta)
+    ///
+    pub fn new(lmax: u16, sintheta: f64, costheta: f64) -> Self {
+        let size = Self::index(lmax, lmax) + 1;
+        let mut table = LegendreTable { lmax, plm: vec![0.0; size], deriv1: vec![0.0; size], deriv2: vec![0.0; size] };
+        table.update(sintheta, costheta);
+        return table;
+    }
+
+
+
+
+    /// Recomputes the table for another theta, reusing the allocated memory
+    ///
+    /// # Arguments
+    ///
+    /// * `sintheta` - sin(theta)
+    /// * `costheta` - cos(theta)
+    ///
+    pub fn update(&mut self, sintheta: f64, costheta: f64) {
+
+        let lmax = self.lmax;
+
+        // The functions themselves, with the recurrence in l for each m
+
+        let mut pmm: f64 = 1.0;
+        for m in 0..=lmax {
+            if m > 0 {
+                pmm *= f64::from(2*m - 1) * sintheta;
+            }
+            self.plm[Self::index(m, m)] = pmm;
+            if m < lmax {
+                self.plm[Self::index(m+1, m)] = f64::from(2*m + 1) * costheta * pmm;
+            }
+            for l in m+2..=lmax {
+                self.plm[Self::index(l, m)] = (f64::from(2*l - 1) * costheta * self.plm[Self::index(l-1, m)]
+                                              - f64::from(l + m - 1) * self.plm[Self::index(l-2, m)]) / f64::from(l - m);
+            }
+        }
+
+        // The first and second derivatives with the ladder relations
+        //     dP_l^0/dtheta = -P_l^1
+        //     dP_l^m/dtheta = ((l+m)(l-m+1) P_l^{m-1} - P_l^{m+1}) / 2
+
+        for l in 0..=lmax {
+            for m in 0..=l {
+                self.deriv1[Self::index(l, m)] = Self::ladder(l, m, &self.plm);
+            }
+            for m in 0..=l {
+                self.deriv2[Self::index(l, m)] = Self::ladder(l, m, &self.deriv1);
+            }
+        }
+    }
+
+
+
+
+    /// Returns the maximum degree of the table
+    ///
+    pub fn lmax(&self) -> u16 {
+        return self.lmax;
+    }
+
+
+
+
+    /// Returns P_l^m(cos(theta)), 0 <= m <= l <= lmax
+    ///
+    pub fn plm(&self, l: u16, m: u16) -> f64 {
+        assert!(m <= l && l <= self.lmax, "LegendreTable::plm(): need m <= l <= lmax");
+        return self.plm[Self::index(l, m)];
+    }
+
+
+
+
+    /// Returns dP_l^m(cos(theta))/dtheta, 0 <= m <= l <= lmax
+    ///
+    pub fn deriv1(&self, l: u16, m: u16) -> f64 {
+        assert!(m <= l && l <= self.lmax, "LegendreTable::deriv1(): need m <= l <= lmax");
+        return self.deriv1[Self::index(l, m)];
+    }
+
+
+
+
+    /// Returns d^2P_l^m(cos(theta))/dtheta^2, 0 <= m <= l <= lmax
+    ///
+    pub fn deriv2(&self, l: u16, m: u16) -> f64 {
+        assert!(m <= l && l <= self.lmax, "LegendreTable::deriv2(): need m <= l <= lmax");
+        return self.deriv2[Self::index(l, m)];
+    }
+
+
+
+
+    /// Returns the position of (l, m) in the flattened table
+    ///
+    fn index(l: u16, m: u16) -> usize {
+        return usize::from(l) * (usize::from(l) + 1) / 2 + usize::from(m);
+    }
+
+
+
+
+    /// Applies the ladder relation for d/dtheta to the functions `values` of degree l
+    ///
+    fn ladder(l: u16, m: u16, values: &[f64]) -> f64 {
+        let plus = if m < l { values[Self::index(l, m+1)] } else { 0.0 };
+        if m == 0 {
+            return -plus;
+        }
+        return 0.5 * (f64::from(l + m) * f64::from(l - m + 1) * values[Self::index(l, m-1)] - plus);
+    }
+}
+
+
+
+
+
+
+
+
+
+
+
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sphericalharmonics::*;
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn test_legendre_table() {
+        for theta in [0.0_f64, 0.4, 1.9, std::f64::consts::PI] {
+            let (sintheta, costheta) = (theta.sin(), theta.cos());
+            let table = LegendreTable::new(8, sintheta, costheta);
+            for l in 0..=8 {
+                for m in 0..=l {
+                    let expected = [plmcos(l, m, sintheta, costheta),
+                                    deriv1_plmcos_dtheta_polesafe(l, m, sintheta, costheta),
+                                    deriv2_plmcos_dtheta_polesafe(l, m, sintheta, costheta)];
+                    assert_approx_eq!(table.plm(l, m), expected[0], 1.0e-10 * (1.0 + expected[0].abs()));
+                    assert_approx_eq!(table.deriv1(l, m), expected[1], 1.0e-10 * (1.0 + expected[1].abs()));
+                    assert_approx_eq!(table.deriv2(l, m), expected[2], 1.0e-10 * (1.0 + expected[2].abs()));
+                }
+            }
+        }
+    }
+}
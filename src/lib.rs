@@ -1,7 +1,45 @@
+#![allow(clippy::needless_return)]
+
 mod auxilliary;
 mod sphericalharmonics;
+mod legendretable;
+mod wignerd;
+mod harmonicexpansion;
+mod harmonictransform;
 mod displacement;
+mod projection;
+mod rotation;
+mod mode;
+mod limbdarkening;
+mod intrinsicprofile;
+mod lineprofile;
+mod moments;
+mod surfacegrid;
+mod photometry;
+mod modeidentification;
+mod fourierfit;
+mod frequencyanalysis;
+mod timeseries;
+mod convolution;
 
 pub use sphericalharmonics::*;
+pub use legendretable::*;
+pub use wignerd::*;
+pub use harmonicexpansion::*;
+pub use harmonictransform::*;
 pub use displacement::*;
+pub use projection::*;
+pub use rotation::*;
+pub use mode::*;
+pub use limbdarkening::*;
+pub use intrinsicprofile::*;
+pub use lineprofile::*;
+pub use moments::*;
+pub use surfacegrid::*;
+pub use photometry::*;
+pub use modeidentification::*;
+pub use fourierfit::*;
+pub use frequencyanalysis::*;
+pub use timeseries::*;
+pub use convolution::*;
 
@@ -0,0 +1,601 @@
+use is_odd::IsOdd;
+use crate::auxilliary::*;
+use crate::intrinsicprofile::AtmosphereScaling;
+
+
+// All limb darkening laws give the specific intensity relative to the one at the disk centre,
+// I(mu)/I(1), where mu is the cosine of the angle between the line of sight and the surface normal.
+// The Legendre-weighted integrals follow the convention of Daszynska-Daszkiewicz et al. (2002, A&A 392, 151):
+//     b_l = \int_0^1 h(mu) mu P_l(mu) dmu,   with   h(mu) = I(mu) / \int_0^1 I(mu) mu dmu
+// so that b_0 = 1.
+
+
+
+
+
+/// A limb darkening law
+///
+/// Only intensity() needs to be implemented. The other methods integrate it numerically,
+/// but implementors can override them with analytic expressions.
+///
+pub trait LimbDarkening {
+
+    /// Returns the intensity I(mu)/I(1) for 0 <= mu <= 1
+    ///
+    fn intensity(&self, mu: f64) -> f64;
+
+    /// Returns the intensity I(mu) of a surface element with effective temperature `teff` [K] and
+    /// gravity `logg` [cgs], relative to I(1) of the unperturbed atmosphere. By default the law
+    /// does not depend on the atmosphere, see ScaledLimbDarkening.
+    ///
+    fn intensity_at(&self, mu: f64, _teff: f64, _logg: f64) -> f64 {
+        return self.intensity(mu);
+    }
+
+    /// Returns the disk-integrated flux normalisation \int_0^1 I(mu)/I(1) mu dmu.
+    /// The flux of the star is 2 pi I(1) R^2 / d^2 times this value.
+    ///
+    fn flux_normalisation(&self) -> f64 {
+        return integrate_numerically(|mu| self.intensity(mu) * mu, 0);
+    }
+
+    /// Returns the Legendre-weighted integral b_l = \int_0^1 h(mu) mu P_l(mu) dmu
+    ///
+    fn legendre_integral(&self, l: u16) -> f64 {
+        return integrate_numerically(|mu| self.intensity(mu) * mu, l) / self.flux_normalisation();
+    }
+}
+
+
+
+
+
+
+
+
+
+/// Linear law: I(mu)/I(1) = 1 - u (1 - mu)
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LinearLaw {
+    pub u: f64,
+}
+
+impl LimbDarkening for LinearLaw {
+
+    fn intensity(&self, mu: f64) -> f64 {
+        1.0 - self.u * (1.0 - mu)
+    }
+
+    fn flux_normalisation(&self) -> f64 {
+        0.5 - self.u / 6.0
+    }
+
+    fn legendre_integral(&self, l: u16) -> f64 {
+        power_series_integral(&[(0.0, 1.0 - self.u), (1.0, self.u)], l) / self.flux_normalisation()
+    }
+}
+
+
+
+
+
+
+
+
+
+/// Quadratic law: I(mu)/I(1) = 1 - a (1 - mu) - b (1 - mu)^2
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct QuadraticLaw {
+    pub a: f64,
+    pub b: f64,
+}
+
+impl LimbDarkening for QuadraticLaw {
+
+    fn intensity(&self, mu: f64) -> f64 {
+        1.0 - self.a * (1.0 - mu) - self.b * (1.0 - mu) * (1.0 - mu)
+    }
+
+    fn flux_normalisation(&self) -> f64 {
+        0.5 - self.a / 6.0 - self.b / 12.0
+    }
+
+    fn legendre_integral(&self, l: u16) -> f64 {
+        let terms = [(0.0, 1.0 - self.a - self.b), (1.0, self.a + 2.0 * self.b), (2.0, -self.b)];
+        power_series_integral(&terms, l) / self.flux_normalisation()
+    }
+}
+
+
+
+
+
+
+
+
+
+/// Square-root law (Diaz-Cordoves & Gimenez 1992): I(mu)/I(1) = 1 - c (1 - mu) - d (1 - sqrt(mu))
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SquareRootLaw {
+    pub c: f64,
+    pub d: f64,
+}
+
+impl LimbDarkening for SquareRootLaw {
+
+    fn intensity(&self, mu: f64) -> f64 {
+        1.0 - self.c * (1.0 - mu) - self.d * (1.0 - mu.sqrt())
+    }
+
+    fn flux_normalisation(&self) -> f64 {
+        0.5 - self.c / 6.0 - self.d / 10.0
+    }
+
+    fn legendre_integral(&self, l: u16) -> f64 {
+        let terms = [(0.0, 1.0 - self.c - self.d), (0.5, self.d), (1.0, self.c)];
+        power_series_integral(&terms, l) / self.flux_normalisation()
+    }
+}
+
+
+
+
+
+
+
+
+
+/// Logarithmic law (Klinglesmith & Sobieski 1970): I(mu)/I(1) = 1 - e (1 - mu) - f mu ln(mu)
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LogarithmicLaw {
+    pub e: f64,
+    pub f: f64,
+}
+
+impl LimbDarkening for LogarithmicLaw {
+
+    fn intensity(&self, mu: f64) -> f64 {
+        if mu <= 0.0 {
+            return 1.0 - self.e;
+        }
+        1.0 - self.e * (1.0 - mu) - self.f * mu * mu.ln()
+    }
+
+    fn flux_normalisation(&self) -> f64 {
+        0.5 - self.e / 6.0 + self.f / 9.0
+    }
+
+    fn legendre_integral(&self, l: u16) -> f64 {
+        let power_part = power_series_integral(&[(0.0, 1.0 - self.e), (1.0, self.e)], l);
+        (power_part - self.f * log_power_integral(2.0, l)) / self.flux_normalisation()
+    }
+}
+
+
+
+
+
+
+
+
+
+/// Exponential law (Claret & Hauschildt 2003): I(mu)/I(1) = 1 - g (1 - mu) - h / (1 - e^mu)
+///
+/// Note that with this definition I(1) differs from 1 by -h/(1-e). The integrals are computed numerically.
+/// The last term diverges as h/mu at the limb, so that mu is raised to at least 1e-6: the intensity is then
+/// finite at mu = 0, and mu I(mu) is continuous except within 1e-6 of the limb, where it drops from h to 0.
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ExponentialLaw {
+    pub g: f64,
+    pub h: f64,
+}
+
+impl LimbDarkening for ExponentialLaw {
+
+    fn intensity(&self, mu: f64) -> f64 {
+
+        // 1/(1 - e^mu) diverges for mu -> 0, so that surface elements exactly at the limb get a finite
+        // intensity, and their contribution mu I(mu) to the flux vanishes instead of being 0 * inf
+
+        let mu = mu.max(1.0e-6);
+        1.0 - self.g * (1.0 - mu) + self.h / mu.exp_m1()
+    }
+}
+
+
+
+
+
+
+
+
+
+/// Four-parameter law of Claret (2000): I(mu)/I(1) = 1 - \sum_{k=1}^4 a_k (1 - mu^{k/2})
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ClaretLaw {
+    pub a: [f64; 4],
+}
+
+impl LimbDarkening for ClaretLaw {
+
+    fn intensity(&self, mu: f64) -> f64 {
+        let sqrt_mu = mu.sqrt();
+        let mut intensity: f64 = 1.0;
+        let mut power: f64 = 1.0;
+        for a_k in self.a.iter() {
+            power *= sqrt_mu;
+            intensity -= a_k * (1.0 - power);
+        }
+        intensity
+    }
+
+    fn flux_normalisation(&self) -> f64 {
+        let mut norm: f64 = 0.5;
+        for (k, a_k) in self.a.iter().enumerate() {
+            norm -= a_k * (0.5 - 2.0 / (k as f64 + 5.0));
+        }
+        norm
+    }
+
+    fn legendre_integral(&self, l: u16) -> f64 {
+        let mut terms = vec![(0.0, 1.0 - self.a.iter().sum::<f64>())];
+        for (k, a_k) in self.a.iter().enumerate() {
+            terms.push((0.5 * (k + 1) as f64, *a_k));
+        }
+        power_series_integral(&terms, l) / self.flux_normalisation()
+    }
+}
+
+
+
+
+
+
+
+
+
+/// Tabulated law, linearly interpolated between the given values of I(mu)/I(1).
+/// Outside the tabulated range the nearest tabulated intensity is used.
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct TabulatedLaw {
+    mu: Vec<f64>,
+    intensity: Vec<f64>,
+}
+
+
+
+
+impl TabulatedLaw {
+
+    /// Creates a tabulated law
+    ///
+    /// # Arguments
+    ///
+    /// * `mu`        - strictly increasing values of mu in [0, 1]
+    /// * `intensity` - the corresponding values of I(mu)/I(1)
+    ///
+    pub fn new(mu: Vec<f64>, intensity: Vec<f64>) -> Self {
+
+        assert!(mu.len() >= 2, "TabulatedLaw::new(): need at least 2 points");
+        assert!(mu.len() == intensity.len(), "TabulatedLaw::new(): mu and intensity differ in length");
+        assert!(mu.windows(2).all(|w| w[0] < w[1]), "TabulatedLaw::new(): mu not strictly increasing");
+
+        TabulatedLaw { mu, intensity }
+    }
+
+
+
+
+    /// Integrates I(mu) mu P_l(mu) exactly over each linear segment, and extrapolates
+    /// with a constant intensity outside the table.
+    ///
+    fn segment_integral(&self, l: u16) -> f64 {
+
+        let (abscissas, weights) = gauss_legendre(l as usize / 2 + 3);
+        let last = self.mu.len() - 1;
+
+        let mut nodes: Vec<f64> = Vec::with_capacity(self.mu.len() + 2);
+        if self.mu[0] > 0.0 {
+            nodes.push(0.0);
+        }
+        nodes.extend(self.mu.iter().map(|mu| mu.clamp(0.0, 1.0)));
+        if self.mu[last] < 1.0 {
+            nodes.push(1.0);
+        }
+
+        let mut integral: f64 = 0.0;
+        for segment in nodes.windows(2) {
+            let half_width = 0.5 * (segment[1] - segment[0]);
+            let centre = 0.5 * (segment[1] + segment[0]);
+            for (x, w) in abscissas.iter().zip(weights.iter()) {
+                let mu = centre + half_width * x;
+                integral += half_width * w * self.intensity(mu) * mu * legendre_polynomial(l, mu);
+            }
+        }
+
+        return integral;
+    }
+}
+
+
+
+
+impl LimbDarkening for TabulatedLaw {
+
+    fn intensity(&self, mu: f64) -> f64 {
+
+        let last = self.mu.len() - 1;
+        if mu <= self.mu[0] {
+            return self.intensity[0];
+        }
+        if mu >= self.mu[last] {
+            return self.intensity[last];
+        }
+
+        let j = self.mu.partition_point(|x| *x <= mu) - 1;
+        let fraction = (mu - self.mu[j]) / (self.mu[j+1] - self.mu[j]);
+
+        return self.intensity[j] + fraction * (self.intensity[j+1] - self.intensity[j]);
+    }
+
+    fn flux_normalisation(&self) -> f64 {
+        self.segment_integral(0)
+    }
+
+    fn legendre_integral(&self, l: u16) -> f64 {
+        self.segment_integral(l) / self.segment_integral(0)
+    }
+}
+
+
+
+
+
+
+
+
+
+/// A limb darkening law whose intensity scales with the local atmosphere, e.g. with Teff^4 for
+/// the bolometric intensity, so that hot and cool surface elements get a different weight:
+///     I(mu, Teff, log g) = q(Teff, log g) / q_0 * I(mu)
+/// The shape of the law itself does not change.
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScaledLimbDarkening<L: LimbDarkening> {
+    pub law: L,
+    pub scaling: AtmosphereScaling,
+}
+
+
+
+
+impl<L: LimbDarkening> ScaledLimbDarkening<L> {
+
+    /// Lets the intensity of the law `law` scale with the local atmosphere according to `scaling`
+    ///
+    pub fn new(law: L, scaling: AtmosphereScaling) -> Self {
+        ScaledLimbDarkening { law, scaling }
+    }
+}
+
+
+
+
+impl<L: LimbDarkening> LimbDarkening for ScaledLimbDarkening<L> {
+
+    fn intensity(&self, mu: f64) -> f64 {
+        self.law.intensity(mu)
+    }
+
+    fn intensity_at(&self, mu: f64, teff: f64, logg: f64) -> f64 {
+        self.law.intensity(mu) * self.scaling.factor(teff, logg)
+    }
+
+    fn flux_normalisation(&self) -> f64 {
+        self.law.flux_normalisation()
+    }
+
+    fn legendre_integral(&self, l: u16) -> f64 {
+        self.law.legendre_integral(l)
+    }
+}
+
+
+
+
+
+
+
+
+
+/// Computes the Legendre polynomial P_l(x) with the standard three-term recurrence
+///
+fn legendre_polynomial(l: u16, x: f64) -> f64 {
+
+    let mut previous: f64 = 1.0;
+    if l == 0 {
+        return previous;
+    }
+    let mut current = x;
+    for n in 2..=l {
+        let next = (f64::from(2*n-1) * x * current - f64::from(n-1) * previous) / f64::from(n);
+        previous = current;
+        current = next;
+    }
+
+    return current;
+}
+
+
+
+
+
+
+
+
+
+/// Computes \int_0^1 x^s P_l(x) dx for s > -1, using the recurrence (Gradshteyn & Ryzhik 7.126.2)
+///     I_l(s) = (s - l + 2) / (s + l + 1) I_{l-2}(s)
+/// starting from I_0(s) = 1/(s+1) and I_1(s) = 1/(s+2).
+///
+fn power_integral(s: f64, l: u16) -> f64 {
+
+    let mut integral = if l.is_odd() { 1.0 / (s + 2.0) } else { 1.0 / (s + 1.0) };
+    let mut n = 2 + l % 2;
+    while n <= l {
+        integral *= (s - f64::from(n) + 2.0) / (s + f64::from(n) + 1.0);
+        n += 2;
+    }
+
+    return integral;
+}
+
+
+
+
+
+
+
+
+
+/// Computes \int_0^1 x^s ln(x) P_l(x) dx, i.e. the derivative of power_integral() w.r.t. s
+///
+fn log_power_integral(s: f64, l: u16) -> f64 {
+
+    let (mut integral, mut derivative) = if l.is_odd() {
+        (1.0 / (s + 2.0), -1.0 / ((s + 2.0) * (s + 2.0)))
+    } else {
+        (1.0 / (s + 1.0), -1.0 / ((s + 1.0) * (s + 1.0)))
+    };
+
+    let mut n = 2 + l % 2;
+    while n <= l {
+        let ratio = (s - f64::from(n) + 2.0) / (s + f64::from(n) + 1.0);
+        let dratio_ds = f64::from(2*n - 1) / ((s + f64::from(n) + 1.0) * (s + f64::from(n) + 1.0));
+        derivative = ratio * derivative + dratio_ds * integral;
+        integral *= ratio;
+        n += 2;
+    }
+
+    return derivative;
+}
+
+
+
+
+
+
+
+
+
+/// Computes \int_0^1 I(mu) mu P_l(mu) dmu for I(mu) = \sum_p c_p mu^p, given as a list of (p, c_p)
+///
+fn power_series_integral(terms: &[(f64, f64)], l: u16) -> f64 {
+    return terms.iter().map(|(power, coefficient)| coefficient * power_integral(power + 1.0, l)).sum();
+}
+
+
+
+
+
+
+
+
+
+/// Computes \int_0^1 f(mu) P_l(mu) dmu with a Gauss-Legendre quadrature
+///
+fn integrate_numerically<F: Fn(f64) -> f64>(integrand: F, l: u16) -> f64 {
+
+    let (abscissas, weights) = gauss_legendre(64 + l as usize);
+
+    let mut integral: f64 = 0.0;
+    for (x, w) in abscissas.iter().zip(weights.iter()) {
+        let mu = 0.5 * (x + 1.0);
+        integral += 0.5 * w * integrand(mu) * legendre_polynomial(l, mu);
+    }
+
+    return integral;
+}
+
+
+
+
+
+
+
+
+
+
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+
+    // Wrapper that only forwards intensity(), so that the numerical default methods are used
+
+    struct Numerical<'a>(&'a dyn LimbDarkening);
+
+    impl LimbDarkening for Numerical<'_> {
+        fn intensity(&self, mu: f64) -> f64 {
+            self.0.intensity(mu)
+        }
+    }
+
+    #[test]
+    fn test_analytic_integrals_equal_numerical_ones() {
+        let laws: Vec<Box<dyn LimbDarkening>> = vec![Box::new(LinearLaw { u: 0.6 }),
+                                                     Box::new(QuadraticLaw { a: 0.4, b: 0.25 }),
+                                                     Box::new(SquareRootLaw { c: 0.1, d: 0.7 }),
+                                                     Box::new(LogarithmicLaw { e: 0.7, f: 0.2 }),
+                                                     Box::new(ClaretLaw { a: [0.6, -0.3, 0.8, -0.3] })];
+        for law in laws.iter() {
+            let numerical = Numerical(law.as_ref());
+            assert_approx_eq!(law.flux_normalisation(), numerical.flux_normalisation(), 1.0e-6);
+            assert_approx_eq!(law.legendre_integral(0), 1.0, 1.0e-12);
+            for l in 1..=6 {
+                assert_approx_eq!(law.legendre_integral(l), numerical.legendre_integral(l), 1.0e-6);
+            }
+        }
+    }
+
+    #[test]
+    fn test_exponential_law_at_the_limb() {
+        let law = ExponentialLaw { g: 0.6, h: 0.02 };
+        assert!(law.intensity(0.0).is_finite());
+        assert_eq!(0.0 * law.intensity(0.0), 0.0);
+        assert_approx_eq!(law.intensity(0.5), 1.0 - 0.6 * 0.5 - 0.02 / (1.0 - 0.5_f64.exp()), 1.0e-14);
+
+        // A trapezoidal disk integration that includes mu = 0 remains finite and converges to the flux normalisation
+
+        let n = 20000;
+        let sum: f64 = (0..=n).map(|j| {
+            let mu = j as f64 / n as f64;
+            let weight = if j == 0 || j == n { 0.5 } else { 1.0 };
+            weight * law.intensity(mu) * mu
+        }).sum();
+        assert_approx_eq!(sum / n as f64, law.flux_normalisation(), 1.0e-5);
+    }
+
+    #[test]
+    fn test_tabulated_law_reproduces_linear_law() {
+        let linear = LinearLaw { u: 0.55 };
+        let mu: Vec<f64> = (0..=4).map(|j| 0.25 * j as f64).collect();
+        let tabulated = TabulatedLaw::new(mu.clone(), mu.iter().map(|x| linear.intensity(*x)).collect());
+        assert_approx_eq!(tabulated.intensity(0.3), linear.intensity(0.3), 1.0e-14);
+        assert_approx_eq!(tabulated.flux_normalisation(), linear.flux_normalisation(), 1.0e-14);
+        for l in 0..=5 {
+            assert_approx_eq!(tabulated.legendre_integral(l), linear.legendre_integral(l), 1.0e-12);
+        }
+    }
+}
@@ -0,0 +1,258 @@
+use crate::auxilliary::*;
+use crate::mode::*;
+use crate::rotation::*;
+use crate::projection::*;
+use crate::limbdarkening::*;
+use crate::intrinsicprofile::*;
+use crate::surfacegrid::*;
+
+
+
+
+
+/// Synthesises the disk-integrated, continuum-normalised profile of a spectral line
+/// of a non-radially pulsating star.
+///
+/// The stellar surface is covered with a SurfaceGrid in the observer's frame, where the z'-axis
+/// points towards the observer, so that the visible elements are those with a positive z'-component
+/// of the normal. For each visible element the pulsation and rotation velocity is projected onto the
+/// line of sight, and the intrinsic profile is shifted over this velocity and weighted with the limb
+/// darkening and the projected area of the element. Elements that straddle the limb are counted as
+/// visible or not depending on their normal. The local effective temperature and gravity of the
+/// perturbed surface are passed to the intrinsic profile and to the limb darkening law.
+///
+pub struct LineProfileSynthesizer {
+    pub inclination: f64,            // Angle between the rotation axis and the line of sight [rad]
+    pub radius: f64,                 // Stellar radius [R_sun]
+    pub v_eq: f64,                   // Equatorial rotation velocity [km/s]
+    pub grid: Box<dyn SurfaceGrid>,  // Tessellation of the stellar surface in the observer's frame
+    pub teff: f64,                   // Effective temperature [K]
+    pub logg: f64,                   // Surface gravity [log cgs]
+    pub intrinsic_profile: Box<dyn IntrinsicProfile>,
+    pub limb_darkening: Box<dyn LimbDarkening>,
+}
+
+
+
+
+
+
+
+
+
+impl LineProfileSynthesizer {
+
+    /// Creates a synthesizer for a non-rotating star with a default grid of 100 x 200 surface elements
+    /// on the visible hemisphere (see with_grid())
+    ///
+    /// # Arguments
+    ///
+    /// * `inclination`       - angle between the rotation axis and the line of sight [rad]
+    /// * `radius`            - stellar radius [R_sun]
+    /// * `teff`              - effective temperature [K]
+    /// * `logg`              - surface gravity [log cgs]
+    /// * `intrinsic_profile` - the local line profile
+    /// * `limb_darkening`    - the limb darkening law
+    ///
+    pub fn new(inclination: f64, radius: f64, teff: f64, logg: f64,
+               intrinsic_profile: impl IntrinsicProfile + 'static, limb_darkening: impl LimbDarkening + 'static) -> Self {
+
+        LineProfileSynthesizer { inclination, radius, v_eq: 0.0, grid: Box::new(LatitudeBandGrid::new(200, 200)), teff, logg,
+                                 intrinsic_profile: Box::new(intrinsic_profile), limb_darkening: Box::new(limb_darkening) }
+    }
+
+
+
+
+    /// Lets the star rotate rigidly with an equatorial velocity `v_eq` [km/s]. The pulsation velocities
+    /// then include the first-order Coriolis terms, see displacement_coriolis().
+    ///
+    pub fn with_rotation(mut self, v_eq: f64) -> Self {
+        self.v_eq = v_eq;
+        self
+    }
+
+
+
+
+    /// Replaces the default grid by an equal-area LatitudeBandGrid with `n_mu` bands in mu = cos(theta')
+    /// of `n_phi` elements each on the visible hemisphere.
+    ///
+    pub fn with_grid(mut self, n_mu: usize, n_phi: usize) -> Self {
+
+        assert!(n_mu > 0 && n_phi > 0, "LineProfileSynthesizer::with_grid(): empty grid");
+
+        self.grid = Box::new(LatitudeBandGrid::new(2 * n_mu, n_phi));
+        self
+    }
+
+
+
+
+    /// Replaces the default grid by any other tessellation of the stellar surface
+    ///
+    pub fn with_surface_grid(mut self, grid: impl SurfaceGrid + 'static) -> Self {
+        self.grid = Box::new(grid);
+        self
+    }
+
+
+
+
+    /// Returns the stellar mass [M_sun] that follows from the gravity and the radius
+    ///
+    pub fn mass(&self) -> f64 {
+
+        // g is in cm/s^2, i.e. 1e-5 km/s^2
+
+        let radius_km = self.radius * SOLAR_RADIUS_KM;
+        return 1.0e-5 * 10.0_f64.powf(self.logg) * radius_km * radius_km / SOLAR_GM_KM3_S2;
+    }
+
+
+
+
+    /// Computes the normalised line profile of a star pulsating in a set of modes
+    ///
+    /// The returned fluxes are normalised to the continuum, so that 1.0 means no absorption.
+    /// Velocities are radial velocities, i.e. positive when receding from the observer.
+    ///
+    /// # Arguments
+    ///
+    /// * `modes`      - the pulsation modes
+    /// * `time`       - time of the observation [d]
+    /// * `velocities` - velocity grid on which the profile is computed [km/s]
+    ///
+    pub fn profile(&self, modes: &ModeSet, time: f64, velocities: &[f64]) -> Vec<f64> {
+        return self.profile_seen_from(modes, time, velocities, self.inclination, self.v_eq);
+    }
+
+
+
+
+    /// Computes the normalised line profile as profile(), but for the given inclination and equatorial
+    /// rotation velocity instead of those of the synthesizer, e.g. to scan a grid of them
+    ///
+    /// # Arguments
+    ///
+    /// * `modes`       - the pulsation modes
+    /// * `time`        - time of the observation [d]
+    /// * `velocities`  - velocity grid on which the profile is computed [km/s]
+    /// * `inclination` - angle between the rotation axis and the line of sight [rad]
+    /// * `v_eq`        - equatorial rotation velocity [km/s]
+    ///
+    pub fn profile_seen_from(&self, modes: &ModeSet, time: f64, velocities: &[f64], inclination: f64, v_eq: f64) -> Vec<f64> {
+
+        let rotation_frequency = rotation_frequency(v_eq, self.radius);
+        let mass = self.mass();
+
+        let mut absorption = vec![0.0; velocities.len()];
+        let mut total_weight: f64 = 0.0;
+
+        for element in self.grid.elements() {
+
+            // Only the visible elements contribute, weighted with their projected area and the limb darkening
+
+            let mu = element.normal[2];
+            if mu <= 0.0 {
+                continue;
+            }
+            // The local effective temperature selects the intrinsic profile and the limb darkening of the element
+
+            let (theta_obs, phi_obs) = (element.theta(), element.phi());
+            let (theta, phi) = observer_to_star(theta_obs, phi_obs, inclination);
+            let teff = modes.local_teff(time, theta, phi, self.teff);
+            let logg = modes.local_logg(time, theta, phi, self.logg, mass, self.radius);
+            let weight = element.area * mu * self.limb_darkening.intensity_at(mu, teff, logg);
+
+            // The radial velocity is positive when receding, i.e. away from the observer.
+
+            let v_los = -modes.velocity_los_rotating(time, theta_obs, phi_obs, inclination, self.radius, rotation_frequency)
+                        - rotation_velocity_los(theta_obs, phi_obs, inclination, v_eq);
+
+            for (n, velocity) in velocities.iter().enumerate() {
+                absorption[n] += weight * self.intrinsic_profile.absorption(velocity - v_los, teff, logg);
+            }
+            total_weight += weight;
+        }
+
+        return absorption.iter().map(|a| 1.0 - a / total_weight).collect();
+    }
+}
+
+
+
+
+
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::PI;
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn test_profile_without_pulsation() {
+        let synthesizer = LineProfileSynthesizer::new(0.8, 3.0, 20000.0, 4.0, GaussianProfile::new(5.0, 0.6), LinearLaw { u: 0.6 }).with_grid(20, 40);
+        let velocities = [-10.0, -2.5, 0.0, 4.0];
+        let profile = synthesizer.profile(&ModeSet::new(), 0.3, &velocities);
+        for (flux, v) in profile.iter().zip(velocities.iter()) {
+            assert_approx_eq!(*flux, 1.0 - 0.6 * f64::exp(-v * v / 50.0), 1.0e-12);
+        }
+    }
+
+    #[test]
+    fn test_profile_radial_mode_pole_on() {
+
+        // A radial mode seen pole-on at maximum expansion velocity shifts the line to the blue,
+        // so the blue wing must be deeper than the red wing.
+
+        let synthesizer = LineProfileSynthesizer::new(0.0, 3.0, 20000.0, 4.0, GaussianProfile::new(5.0, 0.5), LinearLaw { u: 0.6 }).with_grid(50, 20);
+        let modes = ModeSet::from(vec![Mode::new(0, 0, 5.0, 0.01, 0.0, -0.5 * PI)]);
+        let profile = synthesizer.profile(&modes, 0.0, &[-8.0, 8.0]);
+        assert!(profile[0] < profile[1]);
+    }
+
+    #[test]
+    fn test_profile_local_teff() {
+        use crate::moments::*;
+
+        // A radial mode at maximum temperature with a line depth proportional to Teff^2 deepens the line
+
+        let scaling = AtmosphereScaling { teff_ref: 20000.0, logg_ref: 4.0, alpha: 2.0, beta: 0.0 };
+        let synthesizer = LineProfileSynthesizer::new(0.5, 3.0, 20000.0, 4.0, GaussianProfile::new(5.0, 0.3).with_depth_scaling(scaling),
+                                                      LinearLaw { u: 0.6 }).with_grid(30, 60);
+        let velocities: Vec<f64> = (0..=400).map(|j| -50.0 + 0.25 * j as f64).collect();
+        let modes = ModeSet::from(vec![Mode::new(0, 0, 5.0, 0.01, 0.0, 0.0).with_temperature(5.0, 0.0)]);
+        let hot = profile_moments(&velocities, &synthesizer.profile(&modes, 0.0, &velocities));
+        let unperturbed = profile_moments(&velocities, &synthesizer.profile(&ModeSet::new(), 0.0, &velocities));
+        assert_approx_eq!(hot.equivalent_width / unperturbed.equivalent_width, 1.05 * 1.05, 1.0e-6);
+    }
+
+    #[test]
+    fn test_profile_rotational_broadening() {
+        use crate::moments::*;
+
+        // The second moment of a rotationally broadened line follows from the limb darkening alone
+
+        let inclination = 0.6;
+        let synthesizer = LineProfileSynthesizer::new(inclination, 3.0, 20000.0, 4.0, GaussianProfile::new(5.0, 0.5), LinearLaw { u: 0.6 })
+                          .with_rotation(80.0).with_grid(100, 200);
+        let velocities: Vec<f64> = (0..=400).map(|j| -100.0 + 0.5 * j as f64).collect();
+        let profile = synthesizer.profile(&ModeSet::new(), 0.0, &velocities);
+        let observed = profile_moments(&velocities, &profile);
+        let expected = theoretical_moments(&ModeSet::new(), 0.0, inclination, 80.0 * inclination.sin(), 3.0, 5.0, &LinearLaw { u: 0.6 });
+        assert_approx_eq!(observed.first, 0.0, 1.0e-8);
+        assert_approx_eq!(observed.second, expected.second, 0.1);
+
+        // The other tessellations give the same profile
+
+        let healpix = LineProfileSynthesizer::new(inclination, 3.0, 20000.0, 4.0, GaussianProfile::new(5.0, 0.5), LinearLaw { u: 0.6 })
+                      .with_rotation(80.0).with_surface_grid(HealpixGrid::new(64));
+        let observed = profile_moments(&velocities, &healpix.profile(&ModeSet::new(), 0.0, &velocities));
+        assert_approx_eq!(observed.second, expected.second, 0.5);
+    }
+}
@@ -0,0 +1,639 @@
+use std::f64::consts::PI;
+use crate::auxilliary::*;
+use crate::displacement::*;
+use crate::projection::*;
+use crate::rotation::*;
+
+
+
+
+
+/// Perturbation of the local effective temperature: delta T / T = f_T * Y_l^m * e^{i(omega t + psi_T)}
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TemperaturePerturbation {
+    pub amplitude: f64,              // f_T, relative to the radial displacement amplitude
+    pub phase: f64,                  // psi_T, phase lag w.r.t. the radial displacement [rad]
+}
+
+
+
+
+/// Perturbation of the local effective gravity: delta g / g = f_g * Y_l^m * e^{i(omega t + psi_g)}
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GravityPerturbation {
+    pub amplitude: f64,              // f_g, relative to the radial displacement amplitude
+    pub phase: f64,                  // psi_g, phase lag w.r.t. the radial displacement [rad]
+}
+
+
+
+
+
+
+
+
+
+/// A single non-radial pulsation mode
+///
+/// The radial displacement is ampl_radial * P_l^|m|(cos theta) * cos(2 pi frequency t + m phi + phase),
+/// and the tangential amplitude equals k * ampl_radial. Times are in days.
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct Mode {
+    pub l: u16,                      // Degree l >= 0
+    pub m: i16,                      // Azimuthal number -l <= m <= l
+    pub frequency: f64,              // Pulsation frequency [c/d]
+    pub ampl_radial: f64,            // Radial displacement amplitude * Y_l^m [R]
+    pub k: f64,                      // Ratio of the tangential to the radial amplitude in the absence of rotation
+    pub phase: f64,                  // Phase psi at t = 0 [rad]
+    pub ledoux: f64,                 // Ledoux constant C_nl, used for the Coriolis correction of the tangential amplitude
+    pub temperature: Option<TemperaturePerturbation>,
+    pub gravity: Option<GravityPerturbation>,
+}
+
+
+
+
+
+
+
+
+
+impl Mode {
+
+    /// Creates a mode without temperature or gravity perturbation and with C_nl = 0
+    ///
+    /// # Arguments
+    ///
+    /// * `l`           - degree of the mode >= 0
+    /// * `m`           - azimuthal number of the mode, -l <= m <= l
+    /// * `frequency`   - pulsation frequency [c/d]
+    /// * `ampl_radial` - radial displacement amplitude * Y_l^m [R]
+    /// * `k`           - ratio of the tangential to the radial amplitude
+    /// * `phase`       - phase at t = 0 [rad]
+    ///
+    pub fn new(l: u16, m: i16, frequency: f64, ampl_radial: f64, k: f64, phase: f64) -> Self {
+
+        assert!(m.unsigned_abs() <= l, "Mode::new(): |m| > l");
+
+        Mode { l, m, frequency, ampl_radial, k, phase, ledoux: 0.0, temperature: None, gravity: None }
+    }
+
+
+
+
+    /// Adds a local temperature perturbation with amplitude f_T and phase lag psi_T [rad]
+    ///
+    pub fn with_temperature(mut self, amplitude: f64, phase: f64) -> Self {
+        self.temperature = Some(TemperaturePerturbation { amplitude, phase });
+        self
+    }
+
+
+
+
+    /// Adds a local gravity perturbation with amplitude f_g and phase lag psi_g [rad]
+    ///
+    pub fn with_gravity(mut self, amplitude: f64, phase: f64) -> Self {
+        self.gravity = Some(GravityPerturbation { amplitude, phase });
+        self
+    }
+
+
+
+
+    /// Sets the Ledoux constant C_nl, which corrects the tangential amplitude for the Coriolis force
+    /// in a rotating star, see coriolis_tangential_amplitude()
+    ///
+    pub fn with_ledoux(mut self, ledoux: f64) -> Self {
+        self.ledoux = ledoux;
+        self
+    }
+
+
+
+
+    /// Returns the angular pulsation frequency [rad/s]
+    ///
+    pub fn omega(&self) -> f64 {
+        return 2.0 * PI * self.frequency / SECONDS_PER_DAY;
+    }
+
+
+
+
+    /// Returns the phase omega*t + psi at the time `time` [d]
+    ///
+    pub fn phase_at(&self, time: f64) -> f64 {
+        return 2.0 * PI * self.frequency * time + self.phase;
+    }
+
+
+
+
+    /// Returns the ratio K = G M / (omega^2 R^3) of the tangential to the radial amplitude
+    /// in the absence of rotation.
+    ///
+    /// # Arguments
+    ///
+    /// * `frequency` - pulsation frequency [c/d]
+    /// * `mass`      - stellar mass [M_sun]
+    /// * `radius`    - stellar radius [R_sun]
+    ///
+    pub fn adiabatic_k(frequency: f64, mass: f64, radius: f64) -> f64 {
+
+        let omega = 2.0 * PI * frequency / SECONDS_PER_DAY;
+        let radius_km = radius * SOLAR_RADIUS_KM;
+
+        return mass * SOLAR_GM_KM3_S2 / (omega * omega * radius_km * radius_km * radius_km);
+    }
+
+
+
+
+    /// Returns the frequency in the frame co-rotating with a star that rotates with frequency `rotation_frequency` [c/d]
+    ///
+    pub fn corotating_frequency(&self, rotation_frequency: f64) -> f64 {
+        return inertial_to_corotating(self.frequency, self.m, rotation_frequency);
+    }
+
+
+
+
+    /// Returns the spin parameter Omega/omega, with omega the angular frequency in the co-rotating frame.
+    /// Without rotation the spin parameter is 0, also for a mode with frequency 0. In a rotating star it is
+    /// singular where the co-rotating frequency vanishes, which panics.
+    ///
+    pub fn spin_parameter(&self, rotation_frequency: f64) -> f64 {
+
+        if rotation_frequency == 0.0 {
+            return 0.0;
+        }
+
+        let corotating_frequency = self.corotating_frequency(rotation_frequency);
+        assert!(corotating_frequency != 0.0, "Mode::spin_parameter(): zero frequency in the co-rotating frame");
+
+        return rotation_frequency / corotating_frequency;
+    }
+
+
+
+
+    /// Computes the Lagrangian displacement (delta r, delta theta, delta phi) at time `time` [d]
+    /// in the surface point (theta, phi) [rad]. See displacement() for the conventions.
+    ///
+    pub fn displacement(&self, time: f64, theta: f64, phi: f64) -> (f64, f64, f64) {
+        return self.displacement_rotating(time, theta, phi, 0.0);
+    }
+
+
+
+
+    /// Computes the Lagrangian displacement (delta r, delta theta, delta phi) at time `time` [d] in the
+    /// surface point (theta, phi) [rad] of a star rotating with frequency `rotation_frequency` [c/d],
+    /// including the Coriolis terms with the Ledoux constant of the mode. See displacement_coriolis() for the conventions.
+    ///
+    pub fn displacement_rotating(&self, time: f64, theta: f64, phi: f64, rotation_frequency: f64) -> (f64, f64, f64) {
+        return displacement_coriolis(self.phase_at(time), theta, phi, self.l, self.m, self.ampl_radial, self.k * self.ampl_radial,
+                                     self.spin_parameter(rotation_frequency), self.ledoux);
+    }
+
+
+
+
+    /// Computes the relative temperature perturbation delta T / T at time `time` [d] in the surface
+    /// point (theta, phi) [rad], which is zero for a mode without temperature perturbation.
+    /// See temperature_perturbation() for the conventions.
+    ///
+    pub fn relative_temperature(&self, time: f64, theta: f64, phi: f64) -> f64 {
+        return match self.temperature {
+            Some(perturbation) => temperature_perturbation(self.phase_at(time), theta, phi, self.l, self.m, self.ampl_radial,
+                                                           perturbation.amplitude, perturbation.phase),
+            None => 0.0,
+        };
+    }
+
+
+
+
+    /// Computes the local effective temperature [K] at time `time` [d] in the surface point (theta, phi) [rad]
+    /// of a star with unperturbed effective temperature `teff` [K]
+    ///
+    pub fn local_teff(&self, time: f64, theta: f64, phi: f64, teff: f64) -> f64 {
+        return teff * (1.0 + self.relative_temperature(time, theta, phi));
+    }
+
+
+
+
+    /// Computes the relative gravity perturbation delta g / g at time `time` [d] in the surface point
+    /// (theta, phi) [rad] of a star with mass `mass` [M_sun] and radius `radius` [R_sun]. If the mode has
+    /// an explicit gravity perturbation, delta g / g = f_g ampl_radial P_l^|m| cos(omega t + psi + psi_g + m phi)
+    /// is used, otherwise the adiabatic estimate of gravity_perturbation().
+    ///
+    pub fn relative_gravity(&self, time: f64, theta: f64, phi: f64, mass: f64, radius: f64) -> f64 {
+        return match self.gravity {
+            Some(perturbation) => scalar_perturbation(self.phase_at(time), theta, phi, self.l, self.m, self.ampl_radial,
+                                                      perturbation.amplitude, perturbation.phase),
+            None => gravity_perturbation(self.phase_at(time), theta, phi, self.l, self.m, self.ampl_radial,
+                                         1.0 / Mode::adiabatic_k(self.frequency, mass, radius)),
+        };
+    }
+
+
+
+
+    /// Computes the local surface gravity [log cgs] at time `time` [d] in the surface point (theta, phi) [rad]
+    /// of a star with unperturbed gravity `logg` [log cgs], mass `mass` [M_sun] and radius `radius` [R_sun].
+    /// Panics if delta g / g <= -1, i.e. if the amplitude is too large for a linear perturbation.
+    ///
+    pub fn local_logg(&self, time: f64, theta: f64, phi: f64, logg: f64, mass: f64, radius: f64) -> f64 {
+        return perturbed_logg(logg, self.relative_gravity(time, theta, phi, mass, radius), "Mode::local_logg()");
+    }
+
+
+
+
+    /// Computes the physical components of the pulsation velocity [km/s] at time `time` [d]
+    /// in the surface point (theta, phi) [rad] of a star with radius `radius` [R_sun].
+    ///
+    pub fn velocity(&self, time: f64, theta: f64, phi: f64, radius: f64) -> (f64, f64, f64) {
+        return self.velocity_rotating(time, theta, phi, radius, 0.0);
+    }
+
+
+
+
+    /// Computes the physical components of the pulsation velocity [km/s] w.r.t. the co-rotating frame
+    /// at time `time` [d] in the surface point (theta, phi) [rad] of a star with radius `radius` [R_sun]
+    /// rotating with frequency `rotation_frequency` [c/d]. The rotation velocity itself is not included.
+    ///
+    pub fn velocity_rotating(&self, time: f64, theta: f64, phi: f64, radius: f64, rotation_frequency: f64) -> (f64, f64, f64) {
+
+        let radius_km = radius * SOLAR_RADIUS_KM;
+        let omega = 2.0 * PI * self.corotating_frequency(rotation_frequency) / SECONDS_PER_DAY;
+
+        return velocity_coriolis(self.phase_at(time), theta, phi, self.l, self.m, self.ampl_radial * radius_km,
+                                 self.k * self.ampl_radial * radius_km, self.spin_parameter(rotation_frequency), self.ledoux, omega);
+    }
+
+
+
+
+    /// Computes the line-of-sight component (positive towards the observer) of the pulsation velocity [km/s]
+    /// at time `time` [d] in the point (theta_obs, phi_obs) [rad] of the observer's frame.
+    ///
+    pub fn velocity_los(&self, time: f64, theta_obs: f64, phi_obs: f64, inclination: f64, radius: f64) -> f64 {
+        return self.velocity_los_rotating(time, theta_obs, phi_obs, inclination, radius, 0.0);
+    }
+
+
+
+
+    /// Computes the line-of-sight component (positive towards the observer) of the pulsation velocity [km/s]
+    /// in a star rotating with frequency `rotation_frequency` [c/d], at time `time` [d] in the point
+    /// (theta_obs, phi_obs) [rad] of the observer's frame. The rotation velocity itself is not included.
+    ///
+    pub fn velocity_los_rotating(&self, time: f64, theta_obs: f64, phi_obs: f64, inclination: f64, radius: f64, rotation_frequency: f64) -> f64 {
+
+        let (theta, phi) = observer_to_star(theta_obs, phi_obs, inclination);
+        let (v_r, v_theta, v_phi) = self.velocity_rotating(time, theta, phi, radius, rotation_frequency);
+
+        return line_of_sight_component(v_r, v_theta, v_phi, theta, phi, inclination);
+    }
+}
+
+
+
+
+
+
+
+
+
+/// A set of simultaneously excited pulsation modes, whose displacements and velocities are summed
+///
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ModeSet {
+    pub modes: Vec<Mode>,
+}
+
+
+
+
+
+
+
+
+
+impl ModeSet {
+
+    /// Creates an empty set of modes
+    ///
+    pub fn new() -> Self {
+        ModeSet { modes: Vec::new() }
+    }
+
+
+
+
+    /// Adds a mode to the set
+    ///
+    pub fn push(&mut self, mode: Mode) {
+        self.modes.push(mode);
+    }
+
+
+
+
+    /// Adds a mode to the set, builder style
+    ///
+    pub fn with_mode(mut self, mode: Mode) -> Self {
+        self.modes.push(mode);
+        self
+    }
+
+
+
+
+    /// Returns the number of modes in the set
+    ///
+    pub fn len(&self) -> usize {
+        self.modes.len()
+    }
+
+
+
+
+    /// Returns true if the set contains no modes
+    ///
+    pub fn is_empty(&self) -> bool {
+        self.modes.is_empty()
+    }
+
+
+
+
+    /// Returns an iterator over the modes
+    ///
+    pub fn iter(&self) -> std::slice::Iter<'_, Mode> {
+        self.modes.iter()
+    }
+
+
+
+
+    /// Computes the summed Lagrangian displacement (delta r, delta theta, delta phi) of all modes
+    /// at time `time` [d] in the surface point (theta, phi) [rad].
+    ///
+    pub fn displacement(&self, time: f64, theta: f64, phi: f64) -> (f64, f64, f64) {
+        return self.displacement_rotating(time, theta, phi, 0.0);
+    }
+
+
+
+
+    /// Computes the summed Lagrangian displacement (delta r, delta theta, delta phi) of all modes at time
+    /// `time` [d] in the surface point (theta, phi) [rad] of a star rotating with frequency `rotation_frequency` [c/d].
+    ///
+    pub fn displacement_rotating(&self, time: f64, theta: f64, phi: f64, rotation_frequency: f64) -> (f64, f64, f64) {
+
+        let mut total = (0.0, 0.0, 0.0);
+        for mode in self.modes.iter() {
+            let (delta_r, delta_theta, delta_phi) = mode.displacement_rotating(time, theta, phi, rotation_frequency);
+            total.0 += delta_r;
+            total.1 += delta_theta;
+            total.2 += delta_phi;
+        }
+
+        return total;
+    }
+
+
+
+
+    /// Computes the summed relative temperature perturbation delta T / T of all modes at time `time` [d]
+    /// in the surface point (theta, phi) [rad]
+    ///
+    pub fn relative_temperature(&self, time: f64, theta: f64, phi: f64) -> f64 {
+        return self.modes.iter().map(|mode| mode.relative_temperature(time, theta, phi)).sum();
+    }
+
+
+
+
+    /// Computes the local effective temperature [K] due to all modes at time `time` [d] in the surface point
+    /// (theta, phi) [rad] of a star with unperturbed effective temperature `teff` [K]
+    ///
+    pub fn local_teff(&self, time: f64, theta: f64, phi: f64, teff: f64) -> f64 {
+        return teff * (1.0 + self.relative_temperature(time, theta, phi));
+    }
+
+
+
+
+    /// Computes the summed relative gravity perturbation delta g / g of all modes at time `time` [d] in the
+    /// surface point (theta, phi) [rad] of a star with mass `mass` [M_sun] and radius `radius` [R_sun]
+    ///
+    pub fn relative_gravity(&self, time: f64, theta: f64, phi: f64, mass: f64, radius: f64) -> f64 {
+        return self.modes.iter().map(|mode| mode.relative_gravity(time, theta, phi, mass, radius)).sum();
+    }
+
+
+
+
+    /// Computes the local surface gravity [log cgs] due to all modes at time `time` [d] in the surface point
+    /// (theta, phi) [rad] of a star with unperturbed gravity `logg` [log cgs], mass `mass` [M_sun] and radius `radius` [R_sun].
+    /// Panics if delta g / g <= -1, i.e. if the amplitudes are too large for a linear perturbation.
+    ///
+    pub fn local_logg(&self, time: f64, theta: f64, phi: f64, logg: f64, mass: f64, radius: f64) -> f64 {
+        return perturbed_logg(logg, self.relative_gravity(time, theta, phi, mass, radius), "ModeSet::local_logg()");
+    }
+
+
+
+
+    /// Computes the summed pulsation velocity [km/s] of all modes at time `time` [d] in the
+    /// surface point (theta, phi) [rad] of a star with radius `radius` [R_sun].
+    ///
+    pub fn velocity(&self, time: f64, theta: f64, phi: f64, radius: f64) -> (f64, f64, f64) {
+        return self.velocity_rotating(time, theta, phi, radius, 0.0);
+    }
+
+
+
+
+    /// Computes the summed pulsation velocity [km/s] w.r.t. the co-rotating frame of all modes at time `time` [d]
+    /// in the surface point (theta, phi) [rad] of a star with radius `radius` [R_sun] rotating with frequency
+    /// `rotation_frequency` [c/d].
+    ///
+    pub fn velocity_rotating(&self, time: f64, theta: f64, phi: f64, radius: f64, rotation_frequency: f64) -> (f64, f64, f64) {
+
+        let mut total = (0.0, 0.0, 0.0);
+        for mode in self.modes.iter() {
+            let (v_r, v_theta, v_phi) = mode.velocity_rotating(time, theta, phi, radius, rotation_frequency);
+            total.0 += v_r;
+            total.1 += v_theta;
+            total.2 += v_phi;
+        }
+
+        return total;
+    }
+
+
+
+
+    /// Computes the summed line-of-sight velocity (positive towards the observer) [km/s] of all modes
+    /// at time `time` [d] in the point (theta_obs, phi_obs) [rad] of the observer's frame.
+    ///
+    pub fn velocity_los(&self, time: f64, theta_obs: f64, phi_obs: f64, inclination: f64, radius: f64) -> f64 {
+        return self.velocity_los_rotating(time, theta_obs, phi_obs, inclination, radius, 0.0);
+    }
+
+
+
+
+    /// Computes the summed line-of-sight pulsation velocity (positive towards the observer) [km/s] of all modes
+    /// in a star rotating with frequency `rotation_frequency` [c/d], at time `time` [d] in the point
+    /// (theta_obs, phi_obs) [rad] of the observer's frame. The rotation velocity itself is not included.
+    ///
+    pub fn velocity_los_rotating(&self, time: f64, theta_obs: f64, phi_obs: f64, inclination: f64, radius: f64, rotation_frequency: f64) -> f64 {
+        return self.modes.iter()
+                         .map(|mode| mode.velocity_los_rotating(time, theta_obs, phi_obs, inclination, radius, rotation_frequency))
+                         .sum();
+    }
+}
+
+
+
+
+/// Returns log(g (1 + delta g / g)) [log cgs] for the unperturbed gravity `logg` [log cgs], asserting that
+/// the perturbed gravity remains positive
+///
+fn perturbed_logg(logg: f64, relative_gravity: f64, caller: &str) -> f64 {
+    assert!(relative_gravity > -1.0, "{}: delta g / g = {} <= -1, the amplitude is too large for a linear perturbation", caller, relative_gravity);
+    return logg + (1.0 + relative_gravity).log10();
+}
+
+
+
+
+impl From<Vec<Mode>> for ModeSet {
+    fn from(modes: Vec<Mode>) -> Self {
+        ModeSet { modes }
+    }
+}
+
+
+
+
+impl<'a> IntoIterator for &'a ModeSet {
+    type Item = &'a Mode;
+    type IntoIter = std::slice::Iter<'a, Mode>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.modes.iter()
+    }
+}
+
+
+
+
+
+
+
+
+
+
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn test_modeset_is_sum_of_modes() {
+        let first = Mode::new(2, 1, 6.3, 0.01, 0.05, 0.4);
+        let second = Mode::new(1, -1, 7.8, 0.005, 0.1, 2.0);
+        let modes = ModeSet::new().with_mode(first.clone()).with_mode(second.clone());
+
+        let (time, theta, phi, radius) = (0.123, 0.8, 2.1, 4.5);
+        let total = modes.velocity(time, theta, phi, radius);
+        let v1 = first.velocity(time, theta, phi, radius);
+        let v2 = second.velocity(time, theta, phi, radius);
+        assert_approx_eq!(total.0, v1.0 + v2.0, 1.0e-12);
+        assert_approx_eq!(total.1, v1.1 + v2.1, 1.0e-12);
+        assert_approx_eq!(total.2, v1.2 + v2.2, 1.0e-12);
+
+        // The radial velocity amplitude of a radial mode is 2 pi f R ampl_radial
+
+        let radial = Mode::new(0, 0, 5.0, 0.01, 0.0, 0.0);
+        let expected = 2.0 * PI * 5.0 / SECONDS_PER_DAY * 0.01 * radius * SOLAR_RADIUS_KM;
+        assert_approx_eq!(radial.velocity(0.75 / 5.0, theta, phi, radius).0, expected, 1.0e-10);
+    }
+
+    #[test]
+    fn test_local_teff() {
+
+        // The temperature follows the radial displacement with its own amplitude and phase lag
+
+        let mode = Mode::new(2, 1, 6.3, 0.01, 0.05, 0.4).with_temperature(3.0, 0.5 * PI);
+        let (time, theta, phi) = (0.21, 0.8, 2.1);
+        let (delta_r, _, _) = mode.displacement(time, theta, phi);
+        let (delta_r_later, _, _) = mode.displacement(time + 0.25 / 6.3, theta, phi);
+        assert_approx_eq!(mode.relative_temperature(time, theta, phi), 3.0 * delta_r_later, 1.0e-12);
+        assert!(delta_r.abs() > 1.0e-4);
+
+        let modes = ModeSet::new().with_mode(mode.clone()).with_mode(Mode::new(1, 0, 4.0, 0.02, 0.1, 0.0));
+        assert_approx_eq!(modes.local_teff(time, theta, phi, 8000.0), mode.local_teff(time, theta, phi, 8000.0), 1.0e-9);
+    }
+
+    #[test]
+    fn test_local_logg() {
+
+        // For a radial mode delta g / g = -(2 + omega^2 R^3 / GM) delta r / R
+
+        let (mass, radius) = (8.0, 4.0);
+        let mode = Mode::new(0, 0, 6.0, 0.01, 0.0, 0.3);
+        let (delta_r, _, _) = mode.displacement(0.0, 1.0, 0.0);
+        let expected = -(2.0 + 1.0 / Mode::adiabatic_k(6.0, mass, radius)) * delta_r;
+        assert_approx_eq!(mode.relative_gravity(0.0, 1.0, 0.0, mass, radius), expected, 1.0e-14);
+        assert_approx_eq!(mode.local_logg(0.0, 1.0, 0.0, 4.0, mass, radius), 4.0 + (1.0 + expected).log10(), 1.0e-14);
+
+        // An explicit gravity perturbation replaces the adiabatic estimate
+
+        let explicit = mode.clone().with_gravity(4.0, PI);
+        assert_approx_eq!(explicit.relative_gravity(0.0, 1.0, 0.0, mass, radius), -4.0 * delta_r, 1.0e-14);
+    }
+
+    #[test]
+    fn test_spin_parameter() {
+
+        // A static deformation, i.e. a mode with frequency 0, of a non-rotating star has a finite displacement
+
+        let mode = Mode::new(2, 1, 0.0, 0.01, 0.05, 0.4);
+        assert_eq!(mode.spin_parameter(0.0), 0.0);
+        let (delta_r, delta_theta, delta_phi) = mode.displacement(0.3, 0.8, 2.1);
+        assert!(delta_r.is_finite() && delta_theta.is_finite() && delta_phi.is_finite());
+
+        // Omega / omega with the co-rotating frequency f + m f_rot
+
+        let mode = Mode::new(2, 1, 6.3, 0.01, 0.05, 0.4);
+        assert_approx_eq!(mode.spin_parameter(0.3), 0.3 / mode.corotating_frequency(0.3), 1.0e-14);
+    }
+
+    #[test]
+    #[should_panic(expected = "Mode::local_logg(): delta g / g")]
+    fn test_local_logg_panic() {
+
+        // delta g / g = -f_g ampl_radial = -1.2 over the whole surface at t = 0
+
+        let mode = Mode::new(0, 0, 6.0, 0.3, 0.0, 0.0).with_gravity(4.0, PI);
+        mode.local_logg(0.0, 0.0, 0.0, 4.0, 8.0, 4.0);
+    }
+}
@@ -0,0 +1,375 @@
+use std::f64::consts::PI;
+use num_complex::Complex;
+use crate::auxilliary::*;
+use crate::mode::*;
+use crate::limbdarkening::*;
+use crate::moments::*;
+
+
+// The moment method (Balona 1986; Aerts et al. 1992; Briquet & Aerts 2003) compares the observed time
+// series of the first three moments of a line profile with the theoretical ones of theoretical_moments()
+// for a grid of candidate modes and velocity fields, by means of the discriminant
+//     Gamma = sqrt( 1/(3N) \sum_{t} \sum_{n=1}^3 ((<v^n>_obs(t) - <v^n>_th(t)) / sigma_n(t))^2 )
+// which is close to 1 for a solution that fits the data to within the uncertainties.
+
+
+
+
+
+/// The best solution of the moment method for one candidate mode (l, m)
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MomentSolution {
+    pub l: u16,                      // Degree of the candidate mode
+    pub m: i16,                      // Azimuthal number of the candidate mode
+    pub inclination: f64,            // Angle between the rotation axis and the line of sight [rad]
+    pub vsini: f64,                  // Projected equatorial rotation velocity [km/s]
+    pub intrinsic_width: f64,        // Standard deviation of the intrinsic Gaussian profile [km/s]
+    pub velocity_amplitude: f64,     // Amplitude omega * ampl_radial * R of the pulsation velocity [km/s]
+    pub phase: f64,                  // Phase psi at t = 0 of the mode [rad]
+    pub discriminant: f64,           // The discriminant Gamma
+}
+
+
+
+
+impl MomentSolution {
+
+    /// Returns the candidate as a Mode with the given pulsation frequency [c/d], stellar mass [M_sun]
+    /// and radius [R_sun], with the adiabatic ratio of the tangential to the radial amplitude
+    ///
+    pub fn mode(&self, frequency: f64, mass: f64, radius: f64) -> Mode {
+        let omega = 2.0 * PI * frequency / SECONDS_PER_DAY;
+        let ampl_radial = self.velocity_amplitude / (omega * radius * SOLAR_RADIUS_KM);
+        return Mode::new(self.l, self.m, frequency, ampl_radial, Mode::adiabatic_k(frequency, mass, radius), self.phase);
+    }
+}
+
+
+
+
+
+
+
+
+
+/// Mode identification of a single pulsation frequency with the moment method
+///
+/// For each candidate mode with 0 <= l <= lmax and -l <= m <= l, all combinations of the inclinations,
+/// v sin i, intrinsic widths and velocity amplitudes of the grids are scanned. The ratio of the tangential
+/// to the radial amplitude is the adiabatic one for the given mass and radius. The phase of the mode is
+/// chosen such that the theoretical first moment is in phase with the observed one, which is fitted with
+/// a sinusoid of the given frequency. The observed first moments should therefore be relative to the
+/// systemic velocity. If the theoretical first moment vanishes, e.g. for l = 1, m = +-1 seen pole-on or at
+/// any zero of d^l_{0m}(i), the phase is scanned over n_phases equidistant values instead.
+///
+/// The cost is that of theoretical_moments() times the number of epochs, candidate modes and
+/// grid points, so that the grids are best kept coarse and refined around the best solutions.
+///
+pub struct MomentModeId {
+    pub times: Vec<f64>,                      // Times of the observations [d]
+    pub observed: Vec<Moments>,               // Observed moments at these times
+    pub errors: Vec<Moments>,                 // 1-sigma uncertainties of the observed moments
+    pub frequency: f64,                       // Pulsation frequency [c/d]
+    pub mass: f64,                            // Stellar mass [M_sun]
+    pub radius: f64,                          // Stellar radius [R_sun]
+    pub limb_darkening: Box<dyn LimbDarkening>,
+    pub lmax: u16,                            // Maximum degree of the candidate modes
+    pub inclinations: Vec<f64>,               // [rad]
+    pub vsinis: Vec<f64>,                     // [km/s]
+    pub intrinsic_widths: Vec<f64>,           // [km/s]
+    pub velocity_amplitudes: Vec<f64>,        // [km/s]
+    pub n_phases: usize,                      // Number of phases scanned if the first moment does not fix the phase
+}
+
+
+
+
+
+
+
+
+
+impl MomentModeId {
+
+    /// Creates a search with candidate modes up to l = 4, inclinations from 5 to 90 degrees in steps of
+    /// 5 degrees, and 36 phases where the phase has to be scanned. The grids of v sin i, intrinsic widths and velocity amplitudes have to be set with
+    /// with_vsini(), with_intrinsic_widths() and with_velocity_amplitudes().
+    ///
+    /// # Arguments
+    ///
+    /// * `times`          - times of the observations [d]
+    /// * `observed`       - the observed moments, e.g. from profile_moments_with_errors()
+    /// * `errors`         - the 1-sigma uncertainties of the observed moments
+    /// * `frequency`      - pulsation frequency [c/d]
+    /// * `mass`           - stellar mass [M_sun]
+    /// * `radius`         - stellar radius [R_sun]
+    /// * `limb_darkening` - the limb darkening law
+    ///
+    pub fn new(times: Vec<f64>, observed: Vec<Moments>, errors: Vec<Moments>, frequency: f64, mass: f64, radius: f64,
+               limb_darkening: impl LimbDarkening + 'static) -> Self {
+
+        assert!(!times.is_empty(), "MomentModeId::new(): no observations");
+        assert!(times.len() == observed.len() && times.len() == errors.len(), "MomentModeId::new(): times, moments and errors differ in length");
+
+        let inclinations = (1..=18).map(|j| f64::from(5 * j).to_radians()).collect();
+
+        MomentModeId { times, observed, errors, frequency, mass, radius, limb_darkening: Box::new(limb_darkening), lmax: 4,
+                       inclinations, vsinis: vec![], intrinsic_widths: vec![], velocity_amplitudes: vec![], n_phases: 36 }
+    }
+
+
+
+
+    /// Sets the maximum degree of the candidate modes
+    ///
+    pub fn with_lmax(mut self, lmax: u16) -> Self {
+        self.lmax = lmax;
+        self
+    }
+
+
+
+
+    /// Sets the grid of inclinations [rad]
+    ///
+    pub fn with_inclinations(mut self, inclinations: Vec<f64>) -> Self {
+        self.inclinations = inclinations;
+        self
+    }
+
+
+
+
+    /// Sets the grid of projected equatorial rotation velocities [km/s]
+    ///
+    pub fn with_vsini(mut self, vsinis: Vec<f64>) -> Self {
+        self.vsinis = vsinis;
+        self
+    }
+
+
+
+
+    /// Sets the grid of standard deviations of the intrinsic Gaussian profile [km/s]
+    ///
+    pub fn with_intrinsic_widths(mut self, intrinsic_widths: Vec<f64>) -> Self {
+        self.intrinsic_widths = intrinsic_widths;
+        self
+    }
+
+
+
+
+    /// Sets the grid of pulsation velocity amplitudes omega * ampl_radial * R [km/s]
+    ///
+    pub fn with_velocity_amplitudes(mut self, velocity_amplitudes: Vec<f64>) -> Self {
+        self.velocity_amplitudes = velocity_amplitudes;
+        self
+    }
+
+
+
+
+    /// Sets the number of equidistant phases that are scanned if the theoretical first moment vanishes
+    ///
+    pub fn with_phases(mut self, n_phases: usize) -> Self {
+        assert!(n_phases > 0, "MomentModeId::with_phases(): need at least one phase");
+        self.n_phases = n_phases;
+        self
+    }
+
+
+
+
+    /// Scans all candidate modes and grid points, and returns the best solution of each candidate (l, m),
+    /// ranked from the lowest to the highest discriminant.
+    ///
+    pub fn search(&self) -> Vec<MomentSolution> {
+
+        assert!(!self.inclinations.is_empty() && !self.vsinis.is_empty() && !self.intrinsic_widths.is_empty()
+                && !self.velocity_amplitudes.is_empty(), "MomentModeId::search(): empty parameter grid");
+
+        let observed_first = self.first_moment_amplitude(|n| self.observed[n].first);
+        let k = Mode::adiabatic_k(self.frequency, self.mass, self.radius);
+        let omega = 2.0 * PI * self.frequency / SECONDS_PER_DAY;
+
+        let mut solutions = Vec::new();
+        for l in 0..=self.lmax {
+            for m in -(l as i16)..=(l as i16) {
+
+                let mut best: Option<MomentSolution> = None;
+                for &inclination in self.inclinations.iter() {
+                    for &velocity_amplitude in self.velocity_amplitudes.iter() {
+
+                        // Shift the phase of the mode such that the first moment, which depends neither on
+                        // v sin i nor on the intrinsic width, is in phase with the observed one. If the first
+                        // moment is too small, compared to the velocity amplitude, to fix the phase, scan it.
+
+                        let ampl_radial = velocity_amplitude / (omega * self.radius * SOLAR_RADIUS_KM);
+                        let trial = ModeSet::from(vec![Mode::new(l, m, self.frequency, ampl_radial, k, 0.0)]);
+                        let theoretical_first = self.first_moment_amplitude(|n| first_moment(&trial, self.times[n], inclination,
+                                                                                             self.radius, self.limb_darkening.as_ref()));
+                        let phases: Vec<f64> = if theoretical_first.norm() > 1.0e-6 * velocity_amplitude {
+                            vec![(observed_first / theoretical_first).arg()]
+                        } else {
+                            (0..self.n_phases).map(|j| 2.0 * PI * j as f64 / self.n_phases as f64).collect()
+                        };
+
+                        for &phase in phases.iter() {
+                            let modes = ModeSet::from(vec![Mode::new(l, m, self.frequency, ampl_radial, k, phase)]);
+                            for &vsini in self.vsinis.iter() {
+                                for &intrinsic_width in self.intrinsic_widths.iter() {
+                                    let discriminant = self.discriminant(&modes, inclination, vsini, intrinsic_width);
+                                    if best.is_none_or(|solution| discriminant < solution.discriminant) {
+                                        best = Some(MomentSolution { l, m, inclination, vsini, intrinsic_width, velocity_amplitude,
+                                                                     phase, discriminant });
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+                solutions.push(best.unwrap());
+            }
+        }
+
+        solutions.sort_by(|a, b| a.discriminant.total_cmp(&b.discriminant));
+
+        return solutions;
+    }
+
+
+
+
+    /// Computes the discriminant Gamma between the observed moments and those of the given modes
+    ///
+    fn discriminant(&self, modes: &ModeSet, inclination: f64, vsini: f64, intrinsic_width: f64) -> f64 {
+
+        let mut sum: f64 = 0.0;
+        for ((time, observed), errors) in self.times.iter().zip(self.observed.iter()).zip(self.errors.iter()) {
+            let theoretical = theoretical_moments(modes, *time, inclination, vsini, self.radius, intrinsic_width, self.limb_darkening.as_ref());
+            sum += ((observed.first - theoretical.first) / errors.first).powi(2)
+                 + ((observed.second - theoretical.second) / errors.second).powi(2)
+                 + ((observed.third - theoretical.third) / errors.third).powi(2);
+        }
+
+        return (sum / (3 * self.times.len()) as f64).sqrt();
+    }
+
+
+
+
+    /// Fits the weighted sinusoid a cos(2 pi f t) + b sin(2 pi f t) to the first moments given by `first`
+    /// for each epoch, and returns the complex amplitude a - i b, such that the sinusoid equals
+    /// Re{(a - i b) e^{2 pi i f t}}.
+    ///
+    fn first_moment_amplitude<F: Fn(usize) -> f64>(&self, first: F) -> Complex<f64> {
+
+        // Normal equations of the linear least-squares problem
+
+        let (mut cc, mut cs, mut ss, mut cy, mut sy) = (0.0, 0.0, 0.0, 0.0, 0.0);
+        for (n, (time, errors)) in self.times.iter().zip(self.errors.iter()).enumerate() {
+            let weight = 1.0 / (errors.first * errors.first);
+            let (sine, cosine) = (2.0 * PI * self.frequency * time).sin_cos();
+            let value = first(n);
+            cc += weight * cosine * cosine;
+            cs += weight * cosine * sine;
+            ss += weight * sine * sine;
+            cy += weight * cosine * value;
+            sy += weight * sine * value;
+        }
+
+        let determinant = cc * ss - cs * cs;
+        if determinant == 0.0 {
+            return Complex::new(0.0, 0.0);
+        }
+        let a = (ss * cy - cs * sy) / determinant;
+        let b = (cc * sy - cs * cy) / determinant;
+
+        return Complex::new(a, -b);
+    }
+}
+
+
+
+
+
+
+
+
+
+
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn test_search_recovers_the_mode() {
+
+        let (frequency, mass, radius) = (5.5, 8.0, 5.0);
+        let law = LinearLaw { u: 0.4 };
+        let truth = MomentSolution { l: 2, m: 1, inclination: 50.0_f64.to_radians(), vsini: 20.0, intrinsic_width: 8.0,
+                                     velocity_amplitude: 6.0, phase: 1.2, discriminant: 0.0 };
+        let modes = ModeSet::from(vec![truth.mode(frequency, mass, radius)]);
+
+        let times: Vec<f64> = (0..12).map(|n| 0.017 * n as f64).collect();
+        let observed = times.iter().map(|t| theoretical_moments(&modes, *t, truth.inclination, truth.vsini, radius, truth.intrinsic_width, &law))
+                            .collect();
+        let errors = vec![Moments { equivalent_width: 1.0, first: 0.3, second: 10.0, third: 500.0 }; times.len()];
+
+        let solutions = MomentModeId::new(times, observed, errors, frequency, mass, radius, law)
+                        .with_lmax(2)
+                        .with_inclinations(vec![30.0_f64.to_radians(), 50.0_f64.to_radians(), 70.0_f64.to_radians()])
+                        .with_vsini(vec![10.0, 20.0])
+                        .with_intrinsic_widths(vec![8.0])
+                        .with_velocity_amplitudes(vec![3.0, 6.0])
+                        .search();
+
+        assert_eq!(solutions.len(), 9);
+        assert_eq!((solutions[0].l, solutions[0].m), (2, 1));
+        assert_approx_eq!(solutions[0].inclination, truth.inclination, 1.0e-12);
+        assert_approx_eq!(solutions[0].vsini, truth.vsini, 1.0e-12);
+        assert_approx_eq!(solutions[0].velocity_amplitude, truth.velocity_amplitude, 1.0e-12);
+        assert_approx_eq!(solutions[0].phase, truth.phase, 1.0e-9);
+        assert_approx_eq!(solutions[0].discriminant, 0.0, 1.0e-8);
+        assert!(solutions[1].discriminant > 0.5);
+    }
+
+    #[test]
+    fn test_phase_is_scanned_without_first_moment() {
+
+        // At i = 90 degrees d^2_{01}(i) = 0, so that the l = 2, m = 1 mode has no first moment variation,
+        // and its phase can only be found from the higher moments
+
+        let (frequency, mass, radius) = (5.5, 8.0, 5.0);
+        let law = LinearLaw { u: 0.4 };
+        let truth = MomentSolution { l: 2, m: 1, inclination: 0.5 * PI, vsini: 20.0, intrinsic_width: 8.0,
+                                     velocity_amplitude: 6.0, phase: 2.0 * PI * 7.0 / 36.0, discriminant: 0.0 };
+        let modes = ModeSet::from(vec![truth.mode(frequency, mass, radius)]);
+
+        let times: Vec<f64> = (0..12).map(|n| 0.017 * n as f64).collect();
+        let observed: Vec<Moments> = times.iter()
+                                          .map(|t| theoretical_moments(&modes, *t, truth.inclination, truth.vsini, radius, truth.intrinsic_width, &law))
+                                          .collect();
+        assert!(observed.iter().all(|moments| moments.first.abs() < 1.0e-6));
+        let errors = vec![Moments { equivalent_width: 1.0, first: 0.3, second: 10.0, third: 500.0 }; times.len()];
+
+        let solutions = MomentModeId::new(times, observed, errors, frequency, mass, radius, law)
+                        .with_lmax(2)
+                        .with_inclinations(vec![0.5 * PI])
+                        .with_vsini(vec![20.0])
+                        .with_intrinsic_widths(vec![8.0])
+                        .with_velocity_amplitudes(vec![6.0])
+                        .search();
+
+        let solution = solutions.iter().find(|solution| (solution.l, solution.m) == (2, 1)).unwrap();
+        assert_approx_eq!(solution.phase, truth.phase, 1.0e-12);
+        assert_approx_eq!(solution.discriminant, 0.0, 1.0e-8);
+    }
+}
@@ -0,0 +1,313 @@
+use std::f64::consts::PI;
+use crate::auxilliary::*;
+use crate::sphericalharmonics::*;
+use crate::legendretable::*;
+use crate::mode::*;
+use crate::limbdarkening::*;
+
+
+// The moments follow Aerts, De Pauw & Waelkens (1992, A&A 266, 294): the n-th normalised moment is
+//     <v^n> = \int v^n (1 - F(v)) dv / \int (1 - F(v)) dv
+// with velocities counted positive when receding from the observer.
+
+
+
+
+
+/// The equivalent width and the first three normalised moments of a line profile
+///
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Moments {
+    pub equivalent_width: f64,       // \int (1 - F(v)) dv [km/s]
+    pub first: f64,                  // <v>   [km/s]
+    pub second: f64,                 // <v^2> [km^2/s^2]
+    pub third: f64,                  // <v^3> [km^3/s^3]
+}
+
+
+
+
+
+
+
+
+
+/// Computes the moments of a continuum-normalised line profile
+///
+/// # Arguments
+///
+/// * `velocities` - strictly increasing velocities [km/s]
+/// * `fluxes`     - the continuum-normalised fluxes at these velocities
+///
+pub fn profile_moments(velocities: &[f64], fluxes: &[f64]) -> Moments {
+
+    assert!(velocities.len() == fluxes.len(), "profile_moments(): velocities and fluxes differ in length");
+    assert!(velocities.len() >= 2, "profile_moments(): need at least 2 points");
+
+    let weights = trapezoid_weights(velocities);
+
+    let mut sums = [0.0; 4];
+    for ((v, flux), w) in velocities.iter().zip(fluxes.iter()).zip(weights.iter()) {
+        let absorption = w * (1.0 - flux);
+        sums[0] += absorption;
+        sums[1] += absorption * v;
+        sums[2] += absorption * v * v;
+        sums[3] += absorption * v * v * v;
+    }
+
+    return Moments { equivalent_width: sums[0], first: sums[1] / sums[0], second: sums[2] / sums[0], third: sums[3] / sums[0] };
+}
+
+
+
+
+
+
+
+
+
+/// Computes the moments of a continuum-normalised line profile and their 1-sigma uncertainties,
+/// by propagating independent flux errors through the normalised moments.
+///
+/// # Arguments
+///
+/// * `velocities`  - strictly increasing velocities [km/s]
+/// * `fluxes`      - the continuum-normalised fluxes at these velocities
+/// * `flux_errors` - the 1-sigma uncertainties of the fluxes
+///
+/// Returns (moments, uncertainties)
+///
+pub fn profile_moments_with_errors(velocities: &[f64], fluxes: &[f64], flux_errors: &[f64]) -> (Moments, Moments) {
+
+    assert!(fluxes.len() == flux_errors.len(), "profile_moments_with_errors(): fluxes and errors differ in length");
+
+    let moments = profile_moments(velocities, fluxes);
+    let weights = trapezoid_weights(velocities);
+
+    // For <v^n> = N_n / N_0 the derivative w.r.t. the flux in pixel j is -w_j (v_j^n - <v^n>) / N_0
+
+    let mut variances = [0.0; 4];
+    for ((v, sigma), w) in velocities.iter().zip(flux_errors.iter()).zip(weights.iter()) {
+        let scale = w * sigma / moments.equivalent_width;
+        variances[0] += (w * sigma) * (w * sigma);
+        variances[1] += (scale * (v - moments.first)).powi(2);
+        variances[2] += (scale * (v * v - moments.second)).powi(2);
+        variances[3] += (scale * (v * v * v - moments.third)).powi(2);
+    }
+
+    let errors = Moments { equivalent_width: variances[0].sqrt(), first: variances[1].sqrt(),
+                           second: variances[2].sqrt(), third: variances[3].sqrt() };
+
+    return (moments, errors);
+}
+
+
+
+
+
+
+
+
+
+/// Computes the first moment <v> [km/s] of a pulsating star analytically
+///
+/// Only the k = 0 term of the expansion Y_l^m = \sum_k d^{(l)}_{km}(i) Y_l^k(theta', phi') survives
+/// the integration over the azimuth, and the remaining integral over mu can be written with the
+/// Legendre-weighted limb darkening integrals b_l (Dziembowski 1977):
+///     \int h mu (mu P_l + K (1 - mu^2) dP_l/dmu) dmu = (1 - K l) u_l + K l b_{l-1}
+/// with u_l = \int h mu^2 P_l dmu = ((l+1) b_{l+1} + l b_{l-1}) / (2l+1). Rotation does not contribute.
+///
+/// # Arguments
+///
+/// * `modes`          - the pulsation modes
+/// * `time`           - time of the observation [d]
+/// * `inclination`    - angle between the rotation axis and the line of sight [rad]
+/// * `radius`         - stellar radius [R_sun]
+/// * `limb_darkening` - the limb darkening law
+///
+pub fn first_moment(modes: &ModeSet, time: f64, inclination: f64, radius: f64, limb_darkening: &dyn LimbDarkening) -> f64 {
+
+    let mut first: f64 = 0.0;
+    for mode in modes.iter() {
+
+        let l = mode.l;
+        let b_lminus1 = if l > 0 { limb_darkening.legendre_integral(l - 1) } else { 0.0 };
+        let u_l = (f64::from(l + 1) * limb_darkening.legendre_integral(l + 1) + f64::from(l) * b_lminus1) / f64::from(2*l + 1);
+        let disk_integral = (1.0 - mode.k * f64::from(l)) * u_l + mode.k * f64::from(l) * b_lminus1;
+
+        // The k = 0 term of the expansion, relative to the unnormalised P_l^m used in displacement()
+
+        let projection = dlkm(l.into(), 0, mode.m.into(), inclination) * ylmnorm(l, 0) / ylmnorm(l, mode.m);
+        let amplitude = mode.omega() * mode.ampl_radial * radius * SOLAR_RADIUS_KM;
+
+        // The velocity is the displacement a quarter of a cycle later, and the radial velocity
+        // is positive when receding.
+
+        first -= amplitude * projection * disk_integral * f64::cos(mode.phase_at(time) + 0.5 * PI);
+    }
+
+    return first;
+}
+
+
+
+
+
+
+
+
+
+/// Computes the moments of the line profile of a pulsating star by expanding each mode in the
+/// spherical harmonics of the observer's frame with dlkm(), and integrating the powers of the
+/// radial velocity over the visible disk with a Gauss-Legendre quadrature in theta' and a
+/// trapezoidal rule in phi', which is exact for the trigonometric polynomials in phi'.
+///
+/// The intrinsic profile is taken to be a Gaussian with standard deviation `intrinsic_width`,
+/// which adds sigma^2 to <v^2> and 3 sigma^2 <v> to <v^3>. The equivalent width is normalised to 1.
+///
+/// # Arguments
+///
+/// * `modes`           - the pulsation modes
+/// * `time`            - time of the observation [d]
+/// * `inclination`     - angle between the rotation axis and the line of sight [rad]
+/// * `vsini`           - projected equatorial rotation velocity [km/s]
+/// * `radius`          - stellar radius [R_sun]
+/// * `intrinsic_width` - standard deviation of the intrinsic Gaussian profile [km/s]
+/// * `limb_darkening`  - the limb darkening law
+///
+pub fn theoretical_moments(modes: &ModeSet, time: f64, inclination: f64, vsini: f64, radius: f64,
+                           intrinsic_width: f64, limb_darkening: &dyn LimbDarkening) -> Moments {
+
+    let lmax = modes.iter().map(|mode| mode.l).max().unwrap_or(0) as usize;
+    let n_theta = 3 * lmax + 24;
+    let n_phi = 3 * lmax + 8;
+
+    // Precompute for each mode the expansion coefficients d^{(l)}_{km}(i) N_l^k / N_l^m
+
+    let expansions: Vec<Vec<f64>> = modes.iter().map(|mode| {
+        let l = mode.l as i16;
+        (-l..=l).map(|k| dlkm(mode.l.into(), k.into(), mode.m.into(), inclination) * ylmnorm(mode.l, k) / ylmnorm(mode.l, mode.m))
+                .collect()
+    }).collect();
+
+    let (abscissas, weights) = gauss_legendre(n_theta);
+    let mut legendre = LegendreTable::new(lmax as u16, 0.0, 1.0);
+
+    let mut sums = [0.0; 4];
+    for (x, w) in abscissas.iter().zip(weights.iter()) {
+
+        // Map [-1, 1] onto theta' in [0, pi/2], with dS = sin(theta') dtheta' dphi'
+
+        let theta_obs = 0.25 * PI * (x + 1.0);
+        let (sintheta_obs, costheta_obs) = theta_obs.sin_cos();
+        let weight = 0.25 * PI * w * sintheta_obs * costheta_obs * limb_darkening.intensity(costheta_obs);
+
+        // The theta'-dependent parts of all terms of the expansions
+
+        legendre.update(sintheta_obs, costheta_obs);
+        let radial_parts: Vec<Vec<f64>> = modes.iter().map(|mode| {
+            let l = mode.l as i16;
+            (-l..=l).map(|k| {
+                let plk = legendre.plm(mode.l, k.unsigned_abs());
+                let dplk_dtheta = legendre.deriv1(mode.l, k.unsigned_abs());
+                costheta_obs * plk - mode.k * sintheta_obs * dplk_dtheta
+            }).collect()
+        }).collect();
+
+        for j in 0..n_phi {
+            let phi_obs = 2.0 * PI * j as f64 / n_phi as f64;
+
+            let mut v_rad = -vsini * sintheta_obs * phi_obs.sin();
+            for ((mode, expansion), radial_part) in modes.iter().zip(expansions.iter()).zip(radial_parts.iter()) {
+                let amplitude = mode.omega() * mode.ampl_radial * radius * SOLAR_RADIUS_KM;
+                let phase = mode.phase_at(time) + 0.5 * PI;
+                let l = mode.l as i16;
+                for (n, k) in (-l..=l).enumerate() {
+                    v_rad -= amplitude * expansion[n] * radial_part[n] * f64::cos(phase + f64::from(k) * phi_obs);
+                }
+            }
+
+            sums[0] += weight;
+            sums[1] += weight * v_rad;
+            sums[2] += weight * v_rad * v_rad;
+            sums[3] += weight * v_rad * v_rad * v_rad;
+        }
+    }
+
+    let first = sums[1] / sums[0];
+    let sigma2 = intrinsic_width * intrinsic_width;
+
+    return Moments { equivalent_width: 1.0, first, second: sums[2] / sums[0] + sigma2, third: sums[3] / sums[0] + 3.0 * sigma2 * first };
+}
+
+
+
+
+
+
+
+
+
+/// Computes the trapezoidal integration weights for a possibly unevenly sampled grid
+///
+fn trapezoid_weights(x: &[f64]) -> Vec<f64> {
+
+    let n = x.len();
+    let mut weights = vec![0.0; n];
+    for j in 0..n-1 {
+        let half_step = 0.5 * (x[j+1] - x[j]);
+        weights[j] += half_step;
+        weights[j+1] += half_step;
+    }
+
+    return weights;
+}
+
+
+
+
+
+
+
+
+
+
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lineprofile::*;
+    use crate::intrinsicprofile::*;
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn test_first_moment_analytic_equals_quadrature() {
+        let modes = ModeSet::new().with_mode(Mode::new(2, 1, 6.0, 0.01, 0.1, 0.3))
+                                  .with_mode(Mode::new(1, -1, 4.3, 0.004, 0.6, 1.1))
+                                  .with_mode(Mode::new(0, 0, 8.1, 0.003, 0.0, 2.0));
+        let limb_darkening = QuadraticLaw { a: 0.3, b: 0.2 };
+        for time in [0.0, 0.05, 0.13] {
+            let analytic = first_moment(&modes, time, 0.9, 5.0, &limb_darkening);
+            let moments = theoretical_moments(&modes, time, 0.9, 30.0, 5.0, 4.0, &limb_darkening);
+            assert_approx_eq!(analytic, moments.first, 1.0e-9);
+        }
+    }
+
+    #[test]
+    fn test_theoretical_moments_match_synthetic_profile() {
+        let modes = ModeSet::from(vec![Mode::new(2, 2, 6.0, 0.01, 0.2, 0.7)]);
+        let synthesizer = LineProfileSynthesizer::new(1.0, 4.0, 20000.0, 4.0, GaussianProfile::new(6.0, 0.3), LinearLaw { u: 0.4 })
+                          .with_grid(200, 400);
+        let velocities: Vec<f64> = (0..=600).map(|j| -60.0 + 0.2 * j as f64).collect();
+        let profile = synthesizer.profile(&modes, 0.02, &velocities);
+
+        let observed = profile_moments(&velocities, &profile);
+        let predicted = theoretical_moments(&modes, 0.02, 1.0, 0.0, 4.0, 6.0, &LinearLaw { u: 0.4 });
+        assert_approx_eq!(observed.first, predicted.first, 1.0e-2);
+        assert_approx_eq!(observed.second, predicted.second, 1.0e-1);
+        assert_approx_eq!(observed.third, predicted.third, 1.0);
+    }
+}
@@ -0,0 +1,159 @@
+use num_complex::Complex;
+use crate::sphericalharmonics::*;
+use crate::mode::*;
+use crate::limbdarkening::*;
+use crate::intrinsicprofile::AtmosphereScaling;
+
+
+// The flux variation follows Dziembowski (1977, Acta Astron. 27, 203), Watson (1988, Ap&SS 140, 255) and
+// Daszynska-Daszkiewicz et al. (2002, A&A 392, 151). For a mode with radial displacement
+// delta r / R = ampl_radial P_l^|m|(cos theta) cos(omega t + psi + m phi) only the axisymmetric part in the
+// observer's frame contributes to the flux, and
+//     delta F / F = Re{ ampl_radial d^{(l)}_{0m}(i) N_l^0 / N_l^m b_l (D_T + D_geo + D_g) e^{i(omega t + psi)} }
+// with the temperature term D_T = alpha_T f_T e^{i psi_T}, the geometric term D_geo = (1 - l)(l + 2), which
+// combines the changes of the area and of the orientation of the surface elements and does not depend on
+// the tangential amplitude, and the gravity term D_g = alpha_g delta g / g per unit radial displacement.
+// The sensitivities alpha_T = d ln I / d ln Teff and alpha_g = d ln I / d ln g of the intensity in the
+// passband are the exponents alpha and beta of an AtmosphereScaling. The magnitude variation is
+// delta m = -2.5 / ln(10) delta F / F.
+
+
+
+
+
+/// Computes the complex amplitude A of the relative flux variation delta F / F = Re{A e^{i(omega t + psi)}}
+/// of a mode, so that |A| and arg(A) are the photometric amplitude and the phase w.r.t. the radial
+/// displacement. A mode without temperature perturbation has no temperature term. Without explicit
+/// gravity perturbation the adiabatic delta g / g = -(2 + omega^2 R^3 / (G M)) delta r / R is used.
+///
+/// Amplitude ratios and phase differences between passbands follow from the amplitudes computed
+/// with the limb darkening law and the sensitivities of each passband.
+///
+/// # Arguments
+///
+/// * `mode`           - the pulsation mode
+/// * `inclination`    - angle between the rotation axis and the line of sight [rad]
+/// * `mass`           - stellar mass [M_sun]
+/// * `radius`         - stellar radius [R_sun]
+/// * `limb_darkening` - the limb darkening law in the passband
+/// * `scaling`        - the dependence of the intensity in the passband on Teff and log g
+///
+pub fn flux_amplitude(mode: &Mode, inclination: f64, mass: f64, radius: f64,
+                      limb_darkening: &dyn LimbDarkening, scaling: &AtmosphereScaling) -> Complex<f64> {
+
+    let l = f64::from(mode.l);
+
+    let temperature_term = match mode.temperature {
+        Some(perturbation) => scaling.alpha * Complex::from_polar(perturbation.amplitude, perturbation.phase),
+        None => Complex::new(0.0, 0.0),
+    };
+    let geometric_term = Complex::new((1.0 - l) * (l + 2.0), 0.0);
+    let gravity_term = match mode.gravity {
+        Some(perturbation) => scaling.beta * Complex::from_polar(perturbation.amplitude, perturbation.phase),
+        None => Complex::new(-scaling.beta * (2.0 + 1.0 / Mode::adiabatic_k(mode.frequency, mass, radius)), 0.0),
+    };
+
+    // The k = 0 term of the expansion Y_l^m = \sum_k d^{(l)}_{km}(i) Y_l^k(theta', phi'), relative
+    // to the unnormalised P_l^m used in displacement()
+
+    let projection = dlkm(mode.l.into(), 0, mode.m.into(), inclination) * ylmnorm(mode.l, 0) / ylmnorm(mode.l, mode.m);
+
+    return mode.ampl_radial * projection * limb_darkening.legendre_integral(mode.l) * (temperature_term + geometric_term + gravity_term);
+}
+
+
+
+
+
+
+
+
+
+/// Computes the photometric amplitude and phase (|A|, arg(A)) of a mode, such that
+///     delta F / F = |A| cos(omega t + psi + arg(A))
+/// See flux_amplitude() for the arguments.
+///
+pub fn photometric_amplitude(mode: &Mode, inclination: f64, mass: f64, radius: f64,
+                             limb_darkening: &dyn LimbDarkening, scaling: &AtmosphereScaling) -> (f64, f64) {
+
+    return flux_amplitude(mode, inclination, mass, radius, limb_darkening, scaling).to_polar();
+}
+
+
+
+
+
+
+
+
+
+/// Computes the relative flux variation delta F / F at time `time` [d] of a star pulsating in the modes
+/// `modes`. See flux_amplitude() for the other arguments.
+///
+pub fn relative_flux_variation(modes: &ModeSet, time: f64, inclination: f64, mass: f64, radius: f64,
+                               limb_darkening: &dyn LimbDarkening, scaling: &AtmosphereScaling) -> f64 {
+
+    return modes.iter().map(|mode| {
+        let amplitude = flux_amplitude(mode, inclination, mass, radius, limb_darkening, scaling);
+        (amplitude * Complex::from_polar(1.0, mode.phase_at(time))).re
+    }).sum();
+}
+
+
+
+
+
+
+
+
+
+
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::displacement::*;
+    use crate::projection::*;
+    use crate::surfacegrid::*;
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn test_flux_variation_equals_disk_integration() {
+
+        // Integrate the intensity over the perturbed surface elements and compare with the analytic
+        // expression, for a mode with temperature and adiabatic gravity perturbation
+
+        let (inclination, mass, radius, teff, logg) = (0.8, 2.0, 2.5, 8000.0, 3.9);
+        let law = QuadraticLaw { a: 0.3, b: 0.25 };
+        let scaling = AtmosphereScaling { teff_ref: teff, logg_ref: logg, alpha: 3.2, beta: 0.15 };
+        let mode = Mode::new(2, -1, 8.0, 1.0e-6, 0.4, 0.0).with_temperature(4.0, 2.1);
+        let time = 0.013;
+
+        let grid = LatitudeBandGrid::new(300, 300);
+        let (mut unperturbed, mut perturbed) = (0.0, 0.0);
+        for element in grid.elements() {
+            let (theta, phi) = (element.theta(), element.phi());
+            let mu = line_of_sight_component(1.0, 0.0, 0.0, theta, phi, inclination);
+            if mu > 0.0 {
+                unperturbed += law.intensity(mu) * mu * element.area;
+            }
+
+            let phase = mode.phase_at(time);
+            let (n_r, n_theta, n_phi) = perturbed_normal_mode(phase, theta, phi, mode.l, mode.m, mode.ampl_radial, mode.k * mode.ampl_radial);
+            let mu = line_of_sight_component(n_r, n_theta, n_phi, theta, phi, inclination);
+            if mu > 0.0 {
+                let area = element.area * (1.0 + relative_area_change_mode(phase, theta, phi, mode.l, mode.m, mode.ampl_radial, mode.k * mode.ampl_radial));
+                let factor = scaling.factor(mode.local_teff(time, theta, phi, teff), mode.local_logg(time, theta, phi, logg, mass, radius));
+                perturbed += factor * law.intensity(mu) * mu * area;
+            }
+        }
+
+        let expected = (perturbed - unperturbed) / unperturbed;
+        let computed = relative_flux_variation(&ModeSet::from(vec![mode.clone()]), time, inclination, mass, radius, &law, &scaling);
+        let (amplitude, _) = photometric_amplitude(&mode, inclination, mass, radius, &law, &scaling);
+        assert_approx_eq!(computed / amplitude, expected / amplitude, 1.0e-3);
+    }
+}
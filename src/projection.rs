@@ -0,0 +1,204 @@
+use std::f64::consts::PI;
+use crate::sphericalharmonics::*;
+use crate::displacement::*;
+
+
+// Conventions
+// -----------
+// The observer's frame has its z'-axis pointing towards the observer. The frame of the rotation
+// axis is obtained by rotating the observer's frame over the inclination angle i, such that the
+// direction towards the observer has the coordinates (-sin(i), 0, cos(i)) in the rotation-axis
+// frame. With this choice Y_l^m(theta, phi) = \sum_k d^{(l)}_{km}(i) Y_l^k(theta', phi'), see dlkm().
+
+
+
+
+
+/// Converts a point given in the observer's frame to the frame of the rotation axis
+///
+/// # Arguments:
+/// * `theta_obs`   - colatitude w.r.t. the line of sight [rad]
+/// * `phi_obs`     - azimuth around the line of sight [rad]
+/// * `inclination` - angle between the rotation axis and the line of sight [rad]
+///
+/// Returns (theta, phi), the colatitude and azimuth w.r.t. the rotation axis [rad]
+///
+pub fn observer_to_star(theta_obs: f64, phi_obs: f64, inclination: f64) -> (f64, f64) {
+
+    let (sintheta_obs, costheta_obs) = theta_obs.sin_cos();
+    let (sin_incl, cos_incl) = inclination.sin_cos();
+
+    let x_obs = sintheta_obs * phi_obs.cos();
+    let y_obs = sintheta_obs * phi_obs.sin();
+
+    let x = x_obs * cos_incl - costheta_obs * sin_incl;
+    let z = x_obs * sin_incl + costheta_obs * cos_incl;
+
+    return (z.clamp(-1.0, 1.0).acos(), y_obs.atan2(x))
+}
+
+
+
+
+
+
+
+
+
+/// Computes the component of a vector along the line of sight, counted positive towards the observer
+///
+/// # Arguments:
+/// * `v_r`, `v_theta`, `v_phi` - physical components of the vector in the rotation-axis frame
+/// * `theta`       - colatitude of the surface point w.r.t. the rotation axis [rad]
+/// * `phi`         - azimuth of the surface point w.r.t. the rotation axis [rad]
+/// * `inclination` - angle between the rotation axis and the line of sight [rad]
+///
+pub fn line_of_sight_component(v_r: f64, v_theta: f64, v_phi: f64, theta: f64, phi: f64, inclination: f64) -> f64 {
+
+    let (sintheta, costheta) = theta.sin_cos();
+    let (sinphi, cosphi) = phi.sin_cos();
+    let (sin_incl, cos_incl) = inclination.sin_cos();
+
+    // Scalar products of the unit vectors with the direction (-sin(i), 0, cos(i)) to the observer
+
+    let r_dot_obs = -sintheta * cosphi * sin_incl + costheta * cos_incl;
+    let theta_dot_obs = -costheta * cosphi * sin_incl - sintheta * cos_incl;
+    let phi_dot_obs = sinphi * sin_incl;
+
+    return v_r * r_dot_obs + v_theta * theta_dot_obs + v_phi * phi_dot_obs;
+}
+
+
+
+
+
+
+
+
+
+/// Computes the line-of-sight component (positive towards the observer) of the Lagrangian
+/// displacement of a (l, m) mode, by rotating the point to the rotation-axis frame.
+///
+/// # Arguments:
+/// * `phase`       - omega*t + psi [rad]
+/// * `theta_obs`   - colatitude w.r.t. the line of sight [rad]
+/// * `phi_obs`     - azimuth around the line of sight [rad]
+/// * `inclination` - angle between the rotation axis and the line of sight [rad]
+/// * `l`           - degree of the spherical harmonic >= 0
+/// * `m`           - azimuthal number of the spherical harmonic, -l <= m <= l
+/// * `ampl_radial`     - amplitude in the radial direction * Y_l^m
+/// * `ampl_tangential` - amplitude in the tangential direction * Y_l^m
+///
+#[allow(clippy::too_many_arguments)]
+pub fn displacement_los(phase: f64, theta_obs: f64, phi_obs: f64, inclination: f64, l: u16, m: i16, ampl_radial: f64, ampl_tangential: f64) -> f64 {
+
+    let (theta, phi) = observer_to_star(theta_obs, phi_obs, inclination);
+    let (xi_r, xi_theta, xi_phi) = displacement_polesafe(phase, theta, phi, l, m, ampl_radial, ampl_tangential);
+
+    return line_of_sight_component(xi_r, xi_theta, xi_phi, theta, phi, inclination);
+}
+
+
+
+
+
+
+
+
+
+/// Computes the line-of-sight component (positive towards the observer) of the Lagrangian
+/// displacement of a (l, m) mode, by expanding the mode in spherical harmonics of the observer's frame:
+///     Y_l^m(theta, phi) = \sum_{k=-l}^{+l} d^{(l)}_{km}(i) Y_l^k(theta', phi')
+///
+/// Only the radial and the theta'-components contribute to the projection on the z'-axis:
+///     xi_los = xi_r cos(theta') - xi_theta' sin(theta')
+///
+/// The arguments are the same as for displacement_los().
+///
+#[allow(clippy::too_many_arguments)]
+pub fn displacement_los_dlkm(phase: f64, theta_obs: f64, phi_obs: f64, inclination: f64, l: u16, m: i16, ampl_radial: f64, ampl_tangential: f64) -> f64 {
+
+    let (sintheta_obs, costheta_obs) = theta_obs.sin_cos();
+
+    let mut sum: f64 = 0.0;
+    for k in -(l as i16)..=(l as i16) {
+        let plk = plmcos(l, k.unsigned_abs(), sintheta_obs, costheta_obs);
+        let dplk_dtheta = deriv1_plmcos_dtheta_polesafe(l, k.unsigned_abs(), sintheta_obs, costheta_obs);
+        let radial_part = ampl_radial * costheta_obs * plk - ampl_tangential * sintheta_obs * dplk_dtheta;
+        sum += dlkm(l.into(), k.into(), m.into(), inclination) * ylmnorm(l, k) * radial_part * f64::cos(phase + f64::from(k) * phi_obs);
+    }
+
+    // displacement() uses the unnormalised P_l^m, so remove the normalisation of Y_l^m
+
+    return sum / ylmnorm(l, m);
+}
+
+
+
+
+
+
+
+
+
+/// Computes the line-of-sight component (positive towards the observer) of the pulsation
+/// velocity of a (l, m) mode.
+///
+/// # Arguments:
+/// * `phase`       - omega*t + psi [rad]
+/// * `theta_obs`   - colatitude w.r.t. the line of sight [rad]
+/// * `phi_obs`     - azimuth around the line of sight [rad]
+/// * `inclination` - angle between the rotation axis and the line of sight [rad]
+/// * `l`           - degree of the spherical harmonic >= 0
+/// * `m`           - azimuthal number of the spherical harmonic, -l <= m <= l
+/// * `ampl_radial`     - amplitude in the radial direction * Y_l^m [km]
+/// * `ampl_tangential` - amplitude in the tangential direction * Y_l^m [km]
+/// * `omega`       - angular pulsation frequency [rad/s]
+///
+/// The result is in [km/s]. Note that the astronomical radial velocity has the opposite sign.
+///
+#[allow(clippy::too_many_arguments)]
+pub fn velocity_los(phase: f64, theta_obs: f64, phi_obs: f64, inclination: f64, l: u16, m: i16, ampl_radial: f64, ampl_tangential: f64, omega: f64) -> f64 {
+
+    return omega * displacement_los(phase + 0.5 * PI, theta_obs, phi_obs, inclination, l, m, ampl_radial, ampl_tangential);
+}
+
+
+
+
+
+
+
+
+
+
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn test_observer_to_star() {
+
+        // The line of sight itself lies at a colatitude i from the rotation axis
+
+        let (theta, _) = observer_to_star(0.0, 0.0, 0.6);
+        assert_approx_eq!(theta, 0.6, 1.0e-12);
+    }
+
+    #[test]
+    fn test_displacement_los_dlkm_equals_rotation() {
+        let inclination = 0.75;
+        for (l, m) in [(0, 0), (1, 1), (2, -1), (3, 2), (4, -4), (6, 3)] {
+            for (theta_obs, phi_obs) in [(0.0, 0.0), (0.75, 0.0), (0.3, 0.2), (1.0, 2.5), (1.4, -1.7)] {
+                let rotated = displacement_los(0.9, theta_obs, phi_obs, inclination, l, m, 1.0, 0.3);
+                let expanded = displacement_los_dlkm(0.9, theta_obs, phi_obs, inclination, l, m, 1.0, 0.3);
+                assert_approx_eq!(rotated, expanded, 1.0e-8 * (1.0 + rotated.abs()));
+            }
+        }
+    }
+}
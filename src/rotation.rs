@@ -0,0 +1,143 @@
+use std::f64::consts::PI;
+use crate::auxilliary::*;
+
+
+// The star rotates rigidly in the direction of increasing phi. A mode varies as cos(omega t + m phi + psi),
+// so that in the co-rotating frame with azimuth phi_c = phi - Omega t it varies as cos((omega + m Omega) t + m phi_c + psi).
+// Modes with m > 0 are therefore retrograde and modes with m < 0 prograde.
+
+
+
+
+
+/// Computes the rotation frequency [c/d] of a star with equatorial velocity `v_eq` [km/s] and radius `radius` [R_sun]
+///
+pub fn rotation_frequency(v_eq: f64, radius: f64) -> f64 {
+    return v_eq * SECONDS_PER_DAY / (2.0 * PI * radius * SOLAR_RADIUS_KM);
+}
+
+
+
+
+
+
+
+
+
+/// Converts a pulsation frequency [c/d] in the co-rotating frame to the inertial (observer's) frame
+///
+/// # Arguments:
+/// * `frequency`          - frequency in the co-rotating frame [c/d]
+/// * `m`                  - azimuthal number of the mode
+/// * `rotation_frequency` - rotation frequency of the star [c/d]
+///
+pub fn corotating_to_inertial(frequency: f64, m: i16, rotation_frequency: f64) -> f64 {
+    return frequency - f64::from(m) * rotation_frequency;
+}
+
+
+
+
+
+
+
+
+
+/// Converts a pulsation frequency [c/d] in the inertial (observer's) frame to the co-rotating frame
+///
+/// # Arguments:
+/// * `frequency`          - frequency in the inertial frame [c/d]
+/// * `m`                  - azimuthal number of the mode
+/// * `rotation_frequency` - rotation frequency of the star [c/d]
+///
+pub fn inertial_to_corotating(frequency: f64, m: i16, rotation_frequency: f64) -> f64 {
+    return frequency + f64::from(m) * rotation_frequency;
+}
+
+
+
+
+
+
+
+
+
+/// Computes the frequency [c/d] in the inertial frame of a mode in a slowly rotating star, with the
+/// first-order Coriolis correction of Ledoux (1951): in the co-rotating frame the frequency is shifted
+/// over m C_nl Omega, so that in the inertial frame
+///     f = f_0 - m (1 - C_nl) f_rot
+///
+/// # Arguments:
+/// * `frequency`          - frequency of the mode in the non-rotating star [c/d]
+/// * `m`                  - azimuthal number of the mode
+/// * `rotation_frequency` - rotation frequency of the star [c/d]
+/// * `ledoux`             - Ledoux constant C_nl
+///
+pub fn ledoux_frequency(frequency: f64, m: i16, rotation_frequency: f64, ledoux: f64) -> f64 {
+    return corotating_to_inertial(frequency + f64::from(m) * ledoux * rotation_frequency, m, rotation_frequency);
+}
+
+
+
+
+
+
+
+
+
+/// Computes the line-of-sight component (positive towards the observer) of the rotation velocity [km/s]
+/// in the point (theta_obs, phi_obs) [rad] of the observer's frame (see projection.rs for the conventions).
+///
+/// # Arguments:
+/// * `theta_obs`   - colatitude w.r.t. the line of sight [rad]
+/// * `phi_obs`     - azimuth around the line of sight [rad]
+/// * `inclination` - angle between the rotation axis and the line of sight [rad]
+/// * `v_eq`        - equatorial rotation velocity [km/s]
+///
+pub fn rotation_velocity_los(theta_obs: f64, phi_obs: f64, inclination: f64, v_eq: f64) -> f64 {
+
+    // The rotation axis has the coordinates (sin(i), 0, cos(i)) in the observer's frame, so that the
+    // z'-component of Omega x r equals Omega sin(i) y'.
+
+    return v_eq * inclination.sin() * theta_obs.sin() * phi_obs.sin();
+}
+
+
+
+
+
+
+
+
+
+
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::projection::*;
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn test_frequency_conversions() {
+        let rotation = rotation_frequency(100.0, 4.0);
+        let inertial = corotating_to_inertial(5.2, 2, rotation);
+        assert_approx_eq!(inertial_to_corotating(inertial, 2, rotation), 5.2, 1.0e-14);
+        assert_approx_eq!(ledoux_frequency(5.2, -1, rotation, 0.0), 5.2 + rotation, 1.0e-14);
+        assert_approx_eq!(ledoux_frequency(5.2, -1, rotation, 1.0), 5.2, 1.0e-14);
+    }
+
+    #[test]
+    fn test_rotation_velocity_los() {
+
+        // Project the velocity (0, 0, v_eq sin(theta)) from the rotation-axis frame
+
+        let (theta_obs, phi_obs, inclination) = (0.8, 2.2, 1.1);
+        let (theta, phi) = observer_to_star(theta_obs, phi_obs, inclination);
+        let expected = line_of_sight_component(0.0, 0.0, 50.0 * theta.sin(), theta, phi, inclination);
+        assert_approx_eq!(rotation_velocity_los(theta_obs, phi_obs, inclination, 50.0), expected, 1.0e-12);
+    }
+}
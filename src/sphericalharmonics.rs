@@ -1,4 +1,5 @@
 use is_odd::IsOdd;
+use num_complex::Complex;
 use crate::auxilliary::*;
 
 
@@ -35,7 +36,7 @@ pub fn plmcos(l: u16, m: u16, sintheta: f64, costheta: f64) -> f64 {
 
     // First compute P_m^m(costheta)
 
-    let mut pmmcostheta: f64 = match m {
+    let pmmcostheta: f64 = match m {
         0 => 1.0,
         1 => sintheta,
         2 => 3.0 * sintheta * sintheta,
@@ -47,10 +48,30 @@ pub fn plmcos(l: u16, m: u16, sintheta: f64, costheta: f64) -> f64 {
                 for i in (2*(MAX_ODDFAC_ARG+1)-1..=(2 * m as usize - 1)).step_by(2) {
                     oddfactors *= i as f64;
                 }
-                oddfactors
+                oddfactors * sintheta.powi(i32::from(m))
              },
     };
 
+    // Use a recurrence relation in l to compute Plm(costheta)
+
+    return upward_recurrence(l, m, pmmcostheta, costheta);
+}
+
+
+
+
+
+
+
+
+
+/// Computes P_l^m(cos(theta)) starting from P_m^m(cos(theta)) with the three-term recurrence
+///     (l - m) P_l^m = (2l - 1) cos(theta) P_{l-1}^m - (l + m - 1) P_{l-2}^m
+/// Since the recurrence is linear, it equally applies to P_l^m / sin(theta) if it is started
+/// from P_m^m / sin(theta).
+///
+fn upward_recurrence(l: u16, m: u16, mut pmmcostheta: f64, costheta: f64) -> f64 {
+
     // If l == m we're already done
 
     if l == m {
@@ -74,7 +95,6 @@ pub fn plmcos(l: u16, m: u16, sintheta: f64, costheta: f64) -> f64 {
 
         return plmcostheta;
     }
-
 }
 
 
@@ -118,8 +138,6 @@ pub fn deriv1_plmcos_dtheta(l: u16, m: u16, sintheta: f64, costheta: f64) -> f64
 /// * `sintheta`: sin(theta)
 /// * `costheta`: cos(theta)
 ///
-
-
 pub fn deriv2_plmcos_dtheta(l: u16, m: u16, sintheta: f64, costheta: f64) -> f64 {
 
     let inv_sqr_sintheta = 1.0 / (sintheta * sintheta);
@@ -136,6 +154,378 @@ pub fn deriv2_plmcos_dtheta(l: u16, m: u16, sintheta: f64, costheta: f64) -> f64
 
 
 
+
+
+/// Computes P_l^m(cos(theta)) / sin(theta) for m >= 1, which stays finite at the poles.
+/// The recurrence in l is started from P_m^m / sin(theta) = (2m-1)!! sin^(m-1)(theta), so that
+/// e.g. at theta = 0 the result is l(l+1)/2 for m = 1 and 0 for m >= 2.
+///
+/// # Arguments
+/// 
+/// * `l` - The degree l >= 1
+/// * `m` - The azimuthal number m, 1 <= m <= l
+/// * `sintheta`: sin(theta), may be 0
+/// * `costheta`: cos(theta)
+///
+pub fn plmcos_over_sintheta(l: u16, m: u16, sintheta: f64, costheta: f64) -> f64 {
+
+    assert!(m >= 1, "plmcos_over_sintheta(): m must be at least 1");
+    assert!(m <= l, "plmcos_over_sintheta(): m > l");
+
+    let oddfactors: f64 = (1..m).map(|i| f64::from(2*i + 1)).product();
+    let pmm_over_sintheta = oddfactors * sintheta.powi(i32::from(m) - 1);
+
+    return upward_recurrence(l, m, pmm_over_sintheta, costheta);
+}
+
+
+
+
+
+
+
+
+
+/// Computes P_l^m(cos(theta)) / sin^2(theta) for m >= 2, which stays finite at the poles.
+/// The recurrence in l is started from P_m^m / sin^2(theta) = (2m-1)!! sin^(m-2)(theta), so that
+/// e.g. at theta = 0 the result is (l-1)l(l+1)(l+2)/8 for m = 2 and 0 for m >= 3.
+///
+/// # Arguments
+/// 
+/// * `l` - The degree l >= 2
+/// * `m` - The azimuthal number m, 2 <= m <= l
+/// * `sintheta`: sin(theta), may be 0
+/// * `costheta`: cos(theta)
+///
+pub fn plmcos_over_sin2theta(l: u16, m: u16, sintheta: f64, costheta: f64) -> f64 {
+
+    assert!(m >= 2, "plmcos_over_sin2theta(): m must be at least 2");
+    assert!(m <= l, "plmcos_over_sin2theta(): m > l");
+
+    let oddfactors: f64 = (1..m).map(|i| f64::from(2*i + 1)).product();
+    let pmm_over_sin2theta = oddfactors * sintheta.powi(i32::from(m) - 2);
+
+    return upward_recurrence(l, m, pmm_over_sin2theta, costheta);
+}
+
+
+
+
+
+
+
+
+
+/// Computes P_l^m(cos(theta)) or 0 if m > l, as needed by the ladder relations below
+///
+fn plmcos_or_zero(l: u16, m: u16, sintheta: f64, costheta: f64) -> f64 {
+    if m > l { 0.0 } else { plmcos(l, m, sintheta, costheta) }
+}
+
+
+
+
+
+
+
+
+
+/// Computes the derivative of P_l^m(cos(theta)) with respect to theta with the ladder relations
+///     dP_l^0/dtheta = -P_l^1
+///     dP_l^m/dtheta = ((l+m)(l-m+1) P_l^{m-1} - P_l^{m+1}) / 2      for m >= 1
+/// which do not divide by sin(theta), so that the result is also valid at the poles.
+///
+/// # Arguments
+/// 
+/// * `l` - The degree l >= 0
+/// * `m` - The azimuthal number m, 0 <= m <= l
+/// * `sintheta`: sin(theta), may be 0
+/// * `costheta`: cos(theta)
+///
+pub fn deriv1_plmcos_dtheta_polesafe(l: u16, m: u16, sintheta: f64, costheta: f64) -> f64 {
+
+    assert!(m <= l, "deriv1_plmcos_dtheta_polesafe(): m > l");
+
+    if m == 0 {
+        return -plmcos_or_zero(l, 1, sintheta, costheta);
+    }
+
+    let ladder = f64::from(l+m) * f64::from(l-m+1);
+    return 0.5 * (ladder * plmcos(l, m-1, sintheta, costheta) - plmcos_or_zero(l, m+1, sintheta, costheta));
+}
+
+
+
+
+
+
+
+
+
+/// Computes the 2nd derivative of P_l^m(cos(theta)) with respect to theta by applying the
+/// ladder relations of deriv1_plmcos_dtheta_polesafe() twice, so that the result is also
+/// valid at the poles.
+///
+/// # Arguments
+/// 
+/// * `l` - The degree l >= 0
+/// * `m` - The azimuthal number m, 0 <= m <= l
+/// * `sintheta`: sin(theta), may be 0
+/// * `costheta`: cos(theta)
+///
+pub fn deriv2_plmcos_dtheta_polesafe(l: u16, m: u16, sintheta: f64, costheta: f64) -> f64 {
+
+    assert!(m <= l, "deriv2_plmcos_dtheta_polesafe(): m > l");
+
+    let deriv1_or_zero = |k: u16| if k > l { 0.0 } else { deriv1_plmcos_dtheta_polesafe(l, k, sintheta, costheta) };
+
+    if m == 0 {
+        return -deriv1_or_zero(1);
+    }
+
+    let ladder = f64::from(l+m) * f64::from(l-m+1);
+    return 0.5 * (ladder * deriv1_or_zero(m-1) - deriv1_or_zero(m+1));
+}
+
+
+
+
+
+
+
+
+/// Computes the fully normalised associated Legendre function
+///     Pbar_l^m(cos(theta)) = sqrt{(2l+1)/(4 pi) (l-m)!/(l+m)!} P_l^m(cos(theta))
+/// which is orthonormal in the sense that 2 pi \int_{-1}^{+1} Pbar_l^m(x)^2 dx = 1. Unlike plmcos(),
+/// which overflows for l > 150 or so, it uses the stable recurrences for the normalised functions
+///     Pbar_m^m     = sqrt((2m+1)/(2m)) sin(theta) Pbar_{m-1}^{m-1}
+///     Pbar_{m+1}^m = sqrt(2m+3) cos(theta) Pbar_m^m
+///     Pbar_l^m     = a_lm cos(theta) Pbar_{l-1}^m - b_lm Pbar_{l-2}^m
+/// with a_lm = sqrt((2l-1)(2l+1) / ((l-m)(l+m))) and b_lm = sqrt((2l+1)(l+m-1)(l-m-1) / ((l-m)(l+m)(2l-3))).
+/// Following Holmes & Featherstone (2002, J. Geodesy 76, 279) the intermediate values carry a separate
+/// power-of-two scale factor, so that the sectoral values sin^m(theta) do not underflow for large m
+/// close to the poles. The result is accurate up to l ~ 2000 and beyond.
+///
+/// # Arguments
+/// 
+/// * `l` - The degree l >= 0
+/// * `m` - The azimuthal number m, 0 <= m <= l
+/// * `sintheta`: sin(theta)
+/// * `costheta`: cos(theta)
+/// 
+pub fn plmbar(l: u16, m: u16, sintheta: f64, costheta: f64) -> f64 {
+
+    assert!(m <= l, "plmbar(): m > l");
+
+    return *plmbar_recurrence(l, m, sintheta, costheta, false).last().unwrap();
+}
+
+
+
+
+
+
+
+
+
+/// Computes Pbar_l^m(cos(theta)) / sin(theta) for m >= 1, which stays finite at the poles.
+/// See plmbar() for the normalisation and the recurrences.
+///
+/// # Arguments
+/// 
+/// * `l` - The degree l >= 1
+/// * `m` - The azimuthal number m, 1 <= m <= l
+/// * `sintheta`: sin(theta), may be 0
+/// * `costheta`: cos(theta)
+///
+pub fn plmbar_over_sintheta(l: u16, m: u16, sintheta: f64, costheta: f64) -> f64 {
+
+    assert!(m >= 1, "plmbar_over_sintheta(): m must be at least 1");
+    assert!(m <= l, "plmbar_over_sintheta(): m > l");
+
+    return *plmbar_recurrence(l, m, sintheta, costheta, true).last().unwrap();
+}
+
+
+
+
+
+
+
+
+
+/// Computes the derivative of Pbar_l^m(cos(theta)) with respect to theta with the normalised
+/// ladder relations, which remain valid at the poles:
+///     dPbar_l^0/dtheta = -sqrt(l(l+1)) Pbar_l^1
+///     dPbar_l^m/dtheta = (sqrt((l+m)(l-m+1)) Pbar_l^{m-1} - sqrt((l-m)(l+m+1)) Pbar_l^{m+1}) / 2
+///
+/// # Arguments
+/// 
+/// * `l` - The degree l >= 0
+/// * `m` - The azimuthal number m, 0 <= m <= l
+/// * `sintheta`: sin(theta), may be 0
+/// * `costheta`: cos(theta)
+///
+pub fn deriv1_plmbar_dtheta(l: u16, m: u16, sintheta: f64, costheta: f64) -> f64 {
+
+    assert!(m <= l, "deriv1_plmbar_dtheta(): m > l");
+
+    let (lf, mf) = (f64::from(l), f64::from(m));
+    let plus = if m < l { ((lf - mf) * (lf + mf + 1.0)).sqrt() * plmbar(l, m+1, sintheta, costheta) } else { 0.0 };
+
+    if m == 0 {
+        return -plus;
+    }
+
+    return 0.5 * (((lf + mf) * (lf - mf + 1.0)).sqrt() * plmbar(l, m-1, sintheta, costheta) - plus);
+}
+
+
+
+
+
+
+
+
+
+/// Computes Pbar_l^m(cos(theta)) for all m <= l <= lmax, i.e. one column of the table of normalised
+/// associated Legendre functions, with the recurrences described in plmbar(). Useful for spherical
+/// harmonic transforms, where all degrees are needed for each order m.
+///
+/// # Arguments
+/// 
+/// * `lmax` - The maximum degree
+/// * `m`    - The azimuthal number m, 0 <= m <= lmax
+/// * `sintheta`: sin(theta)
+/// * `costheta`: cos(theta)
+///
+/// Returns the vector [Pbar_m^m, Pbar_{m+1}^m, ..., Pbar_lmax^m]
+///
+pub fn plmbar_column(lmax: u16, m: u16, sintheta: f64, costheta: f64) -> Vec<f64> {
+
+    assert!(m <= lmax, "plmbar_column(): m > lmax");
+
+    return plmbar_recurrence(lmax, m, sintheta, costheta, false);
+}
+
+
+
+
+
+
+
+
+
+/// Computes Pbar_l^m(cos(theta)), or Pbar_l^m(cos(theta)) / sin(theta) if `over_sintheta` is true,
+/// for m <= l <= lmax with the recurrences described in plmbar()
+///
+fn plmbar_recurrence(lmax: u16, m: u16, sintheta: f64, costheta: f64, over_sintheta: bool) -> Vec<f64> {
+
+    let mut column = Vec::with_capacity(usize::from(lmax - m) + 1);
+
+    // The values are represented as value * 2^exponent, rescaling whenever they leave [2^-256, 2^256]
+
+    let big = 2.0_f64.powi(256);
+    let mut exponent: i32 = 0;
+
+    // First the sectoral value Pbar_m^m, starting from Pbar_0^0 = 1/sqrt(4 pi)
+
+    let mut pmm = 0.5 / std::f64::consts::PI.sqrt();
+    for k in 1..=m {
+        let factor = if over_sintheta && k == 1 { 1.0 } else { sintheta };
+        pmm *= (f64::from(2*k + 1) / f64::from(2*k)).sqrt() * factor;
+        if pmm.abs() < 1.0 / big {
+            if pmm == 0.0 {
+                column.resize(usize::from(lmax - m) + 1, 0.0);
+                return column;
+            }
+            pmm *= big;
+            exponent -= 256;
+        }
+    }
+
+    column.push(scale_by_power_of_two(pmm, exponent));
+    if lmax == m {
+        return column;
+    }
+
+    // Then the recurrence in l
+
+    let mut pm1m = f64::from(2*m + 3).sqrt() * costheta * pmm;
+    column.push(scale_by_power_of_two(pm1m, exponent));
+    for i in m+2..=lmax {
+        let (lf, mf) = (f64::from(i), f64::from(m));
+        let a_lm = ((2.0*lf - 1.0) * (2.0*lf + 1.0) / ((lf - mf) * (lf + mf))).sqrt();
+        let b_lm = ((2.0*lf + 1.0) * (lf + mf - 1.0) * (lf - mf - 1.0) / ((lf - mf) * (lf + mf) * (2.0*lf - 3.0))).sqrt();
+        let plm = a_lm * costheta * pm1m - b_lm * pmm;
+        pmm = pm1m;
+        pm1m = plm;
+        if pm1m.abs() > big {
+            pmm /= big;
+            pm1m /= big;
+            exponent += 256;
+        }
+        column.push(scale_by_power_of_two(pm1m, exponent));
+    }
+
+    return column;
+}
+
+
+
+
+
+
+
+
+
+/// Computes N_l^m P_l^{|m|}(cos(theta)), i.e. ylmnorm(l, m) * plmcos(l, |m|, sintheta, costheta) including
+/// the Condon-Shortley phase, so that Y_l^m = normalised_plmcos(l, m, ...) * e^(i m phi). It uses plmbar()
+/// and therefore remains accurate for high degrees where ylmnorm() and plmcos() over- or underflow.
+///
+/// # Arguments
+///
+/// * `l` - The degree l >= 0
+/// * `m` - The azimuthal number m, -l <= m <= l
+/// * `sintheta`: sin(theta)
+/// * `costheta`: cos(theta)
+///
+pub fn normalised_plmcos(l: u16, m: i16, sintheta: f64, costheta: f64) -> f64 {
+
+    assert!(m.unsigned_abs() <= l, "normalised_plmcos(): |m| > l");
+
+    let plm = plmbar(l, m.unsigned_abs(), sintheta, costheta);
+
+    if m > 0 && m.is_odd() {
+        return -plm;
+    } else {
+        return plm;
+    }
+}
+
+
+
+
+
+
+
+
+
+/// Computes value * 2^exponent without intermediate over- or underflow of the power of two
+///
+fn scale_by_power_of_two(value: f64, exponent: i32) -> f64 {
+    let half = exponent / 2;
+    return value * 2.0_f64.powi(half) * 2.0_f64.powi(exponent - half);
+}
+
+
+
+
+
+
+
+
+
 /// Computes the normalisation factor N_l^m of the spherical harmonic Y_l^m so that:
 ///          Y_l^m = N_l^m * P_l^{|m|}(cos(theta)) * e^(i m phi)
 ///
@@ -148,7 +538,7 @@ pub fn ylmnorm(l: u16, m: i16) -> f64 {
 
     // Panic if m is not between -l and l
 
-    assert!(m.abs() as u16 <= l, "ylmnorm: |m| > l");
+    assert!(m.unsigned_abs() <= l, "ylmnorm: |m| > l");
 
     // The following array PRECOMPUTED[0..4][0..4] contains: 
     //   if  (0 <= m <= l <= 4): 
@@ -169,9 +559,9 @@ pub fn ylmnorm(l: u16, m: i16) -> f64 {
 
     if l <= 4 {
         if m > 0 && m.is_odd() { 
-            return -1.0 * PRECOMPUTED[l as usize][m.abs() as usize];
+            return -PRECOMPUTED[l as usize][m.unsigned_abs() as usize];
         } else {
-            return PRECOMPUTED[l as usize][m.abs() as usize];
+            return PRECOMPUTED[l as usize][m.unsigned_abs() as usize];
         }
     } 
 
@@ -181,7 +571,7 @@ pub fn ylmnorm(l: u16, m: i16) -> f64 {
 
     let mut fac_division: f64 = 1.0;                  // (l - |m|)! / (l + |m|)!
     if m != 0 {
-        for i in (l - m.abs() as u16 + 1)..=(l + m.abs() as u16) {
+        for i in (l - m.unsigned_abs() + 1)..=(l + m.unsigned_abs()) {
             fac_division *= f64::from(i); 
         }
         fac_division = 1.0 / fac_division; 
@@ -269,6 +659,164 @@ pub fn dlkm(l: u32, k: i32, m: i32, angle: f64) -> f64 {
 
 
 
+/// Computes the complex spherical harmonic
+///     Y_l^m(theta, phi) = N_l^m P_l^{|m|}(cos(theta)) e^(i m phi)
+/// with the normalisation and the Condon-Shortley phase of ylmnorm(), so that Y_l^{-m} = (-1)^m conj(Y_l^m).
+///
+/// # Arguments
+///
+/// * `l`     - The degree l >= 0
+/// * `m`     - The azimuthal number m, -l <= m <= l
+/// * `theta` - colatitude [rad]
+/// * `phi`   - azimuth [rad]
+///
+pub fn ylm_complex(l: u16, m: i16, theta: f64, phi: f64) -> Complex<f64> {
+    let (sintheta, costheta) = theta.sin_cos();
+    return normalised_plmcos(l, m, sintheta, costheta) * Complex::from_polar(1.0, f64::from(m) * phi);
+}
+
+
+
+
+
+
+
+
+
+/// Computes the real (tesseral) spherical harmonic
+///     Y_lm = sqrt(2) |N_l^m| P_l^m(cos(theta)) cos(m phi)          for m > 0
+///     Y_l0 = N_l^0 P_l(cos(theta))
+///     Y_lm = sqrt(2) |N_l^m| P_l^{|m|}(cos(theta)) sin(|m| phi)    for m < 0
+/// i.e. sqrt(2) (-1)^m times the real or imaginary part of Y_l^{|m|}, so that the Condon-Shortley phase
+/// cancels. The real harmonics are orthonormal on the sphere.
+///
+/// # Arguments
+///
+/// * `l`     - The degree l >= 0
+/// * `m`     - The index m, -l <= m <= l
+/// * `theta` - colatitude [rad]
+/// * `phi`   - azimuth [rad]
+///
+pub fn ylm_real(l: u16, m: i16, theta: f64, phi: f64) -> f64 {
+
+    assert!(m.unsigned_abs() <= l, "ylm_real(): |m| > l");
+
+    let (sintheta, costheta) = theta.sin_cos();
+    let plm = plmbar(l, m.unsigned_abs(), sintheta, costheta);
+
+    return plm * tesseral_azimuthal_part(m, phi);
+}
+
+
+
+
+
+
+
+
+
+/// Computes the horizontal gradient of the complex spherical harmonic Y_l^m of ylm_complex(), i.e.
+///     (dY_l^m/dtheta, 1/sin(theta) dY_l^m/dphi)
+/// so that the horizontal displacement K grad_h Y_l^m has the components K times these. Both
+/// components are computed without dividing by sin(theta), so that they remain finite at the poles.
+///
+/// # Arguments
+///
+/// * `l`     - The degree l >= 0
+/// * `m`     - The azimuthal number m, -l <= m <= l
+/// * `theta` - colatitude [rad]
+/// * `phi`   - azimuth [rad]
+///
+pub fn ylm_gradient(l: u16, m: i16, theta: f64, phi: f64) -> (Complex<f64>, Complex<f64>) {
+
+    assert!(m.unsigned_abs() <= l, "ylm_gradient(): |m| > l");
+
+    let (sintheta, costheta) = theta.sin_cos();
+    let abs_m = m.unsigned_abs();
+    let sign = if m > 0 && m.is_odd() { -1.0 } else { 1.0 };
+    let azimuthal = Complex::from_polar(sign, f64::from(m) * phi);
+
+    let dy_dtheta = deriv1_plmbar_dtheta(l, abs_m, sintheta, costheta) * azimuthal;
+    let dy_dphi_over_sintheta = if m == 0 {
+        Complex::new(0.0, 0.0)
+    } else {
+        Complex::new(0.0, f64::from(m)) * plmbar_over_sintheta(l, abs_m, sintheta, costheta) * azimuthal
+    };
+
+    return (dy_dtheta, dy_dphi_over_sintheta);
+}
+
+
+
+
+
+
+
+
+
+/// Computes the horizontal gradient of the real spherical harmonic Y_lm of ylm_real(), i.e.
+///     (dY_lm/dtheta, 1/sin(theta) dY_lm/dphi)
+/// which remains finite at the poles.
+///
+/// # Arguments
+///
+/// * `l`     - The degree l >= 0
+/// * `m`     - The index m, -l <= m <= l
+/// * `theta` - colatitude [rad]
+/// * `phi`   - azimuth [rad]
+///
+pub fn ylm_real_gradient(l: u16, m: i16, theta: f64, phi: f64) -> (f64, f64) {
+
+    assert!(m.unsigned_abs() <= l, "ylm_real_gradient(): |m| > l");
+
+    let (sintheta, costheta) = theta.sin_cos();
+    let abs_m = m.unsigned_abs();
+
+    let dy_dtheta = deriv1_plmbar_dtheta(l, abs_m, sintheta, costheta) * tesseral_azimuthal_part(m, phi);
+    let dy_dphi_over_sintheta = if m == 0 {
+        0.0
+    } else {
+        plmbar_over_sintheta(l, abs_m, sintheta, costheta) * tesseral_azimuthal_part(-m, phi) * f64::from(-m)
+    };
+
+    return (dy_dtheta, dy_dphi_over_sintheta);
+}
+
+
+
+
+
+
+
+
+
+/// Computes the azimuthal part of the real spherical harmonic: sqrt(2) cos(m phi) for m > 0,
+/// 1 for m = 0, and sqrt(2) sin(|m| phi) for m < 0
+///
+fn tesseral_azimuthal_part(m: i16, phi: f64) -> f64 {
+    if m > 0 {
+        return std::f64::consts::SQRT_2 * (f64::from(m) * phi).cos();
+    } else if m < 0 {
+        return std::f64::consts::SQRT_2 * (f64::from(-m) * phi).sin();
+    } else {
+        return 1.0;
+    }
+}
+
+
+
+
+
+
+
+
+
+
+
+
+
+
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -296,5 +844,111 @@ mod tests {
         let (sintheta, costheta) = (theta.sin(), theta.cos());
         plmcos(4, 5, sintheta, costheta);
     }
+
+    #[test]
+    fn test_plmbar() {
+
+        // For low degrees it equals the normalisation times the unnormalised function, including m > 13
+
+        let theta: f64 = 1.3;
+        let (sintheta, costheta) = (theta.sin(), theta.cos());
+        for (l, m) in [(0, 0), (3, -1), (3, 1), (7, 4), (12, -9), (20, 15), (40, 17)] {
+            let expected = ylmnorm(l, m) * plmcos(l, m.unsigned_abs(), sintheta, costheta);
+            assert_approx_eq!(normalised_plmcos(l, m, sintheta, costheta), expected, 1.0e-8 * expected.abs().max(1.0e-3));
+        }
+
+        // For l = 2000 the functions are still orthonormal
+
+        let (abscissas, weights) = crate::auxilliary::gauss_legendre(2001);
+        for m in [0, 1, 700, 1999, 2000] {
+            let norm: f64 = abscissas.iter().zip(weights.iter())
+                                     .map(|(x, w)| w * plmbar(2000, m, (1.0 - x * x).sqrt(), *x).powi(2))
+                                     .sum();
+            assert_approx_eq!(2.0 * std::f64::consts::PI * norm, 1.0, 1.0e-10);
+        }
+    }
+
+    #[test]
+    fn test_ylm_evaluators() {
+        let (theta, phi): (f64, f64) = (1.1, 0.7);
+        let (sintheta, costheta) = (theta.sin(), theta.cos());
+        let step = 1.0e-6;
+        for (l, m) in [(0, 0), (1, 1), (2, -1), (3, 2), (5, -4)] {
+
+            // The complex harmonic combines ylmnorm() and plmcos(), and Y_l^{-m} = (-1)^m conj(Y_l^m)
+
+            let ylm = ylm_complex(l, m, theta, phi);
+            let expected = ylmnorm(l, m) * plmcos(l, m.unsigned_abs(), sintheta, costheta);
+            assert_approx_eq!(ylm.re, expected * (f64::from(m) * phi).cos(), 1.0e-8);
+            assert_approx_eq!(ylm.im, expected * (f64::from(m) * phi).sin(), 1.0e-8);
+            let sign = if m.is_odd() { -1.0 } else { 1.0 };
+            assert_approx_eq!((ylm_complex(l, -m, theta, phi) - sign * ylm.conj()).norm(), 0.0, 1.0e-12);
+
+            // The real harmonic is sqrt(2) (-1)^m times the real or imaginary part of Y_l^{|m|}
+
+            let ylabsm = ylm_complex(l, m.abs(), theta, phi);
+            let expected_real = if m > 0 { std::f64::consts::SQRT_2 * sign * ylabsm.re }
+                                else if m < 0 { std::f64::consts::SQRT_2 * sign * ylabsm.im }
+                                else { ylabsm.re };
+            assert_approx_eq!(ylm_real(l, m, theta, phi), expected_real, 1.0e-12);
+
+            // The gradients agree with central differences
+
+            let (dy_dtheta, dy_dphi) = ylm_gradient(l, m, theta, phi);
+            let numeric_theta = (ylm_complex(l, m, theta + step, phi) - ylm_complex(l, m, theta - step, phi)) / (2.0 * step);
+            let numeric_phi = (ylm_complex(l, m, theta, phi + step) - ylm_complex(l, m, theta, phi - step)) / (2.0 * step * sintheta);
+            assert_approx_eq!((dy_dtheta - numeric_theta).norm(), 0.0, 1.0e-8);
+            assert_approx_eq!((dy_dphi - numeric_phi).norm(), 0.0, 1.0e-8);
+
+            let (dyreal_dtheta, dyreal_dphi) = ylm_real_gradient(l, m, theta, phi);
+            assert_approx_eq!(dyreal_dtheta, (ylm_real(l, m, theta + step, phi) - ylm_real(l, m, theta - step, phi)) / (2.0 * step), 1.0e-8);
+            assert_approx_eq!(dyreal_dphi, (ylm_real(l, m, theta, phi + step) - ylm_real(l, m, theta, phi - step)) / (2.0 * step * sintheta), 1.0e-8);
+        }
+
+        // At the pole only |m| = 1 has a non-zero gradient
+
+        let (dy_dtheta, dy_dphi) = ylm_gradient(4, 2, 0.0, phi);
+        assert!(dy_dtheta.norm() < 1.0e-15 && dy_dphi.norm() < 1.0e-15);
+        let (dy_dtheta, dy_dphi) = ylm_gradient(4, 1, 0.0, phi);
+        assert!(dy_dtheta.norm().is_finite() && dy_dtheta.norm() > 0.1 && dy_dphi.norm().is_finite());
+    }
+
+    #[test]
+    fn test_polesafe_legendre() {
+
+        // Away from the poles the pole-safe versions agree with the original ones
+
+        let theta: f64 = 0.9;
+        let (sintheta, costheta) = (theta.sin(), theta.cos());
+        for (l, m) in [(0, 0), (1, 1), (3, 0), (3, 1), (4, 2), (6, 5)] {
+            assert_approx_eq!(deriv1_plmcos_dtheta_polesafe(l, m, sintheta, costheta), deriv1_plmcos_dtheta(l, m, sintheta, costheta), 1.0e-9);
+            assert_approx_eq!(deriv2_plmcos_dtheta_polesafe(l, m, sintheta, costheta), deriv2_plmcos_dtheta(l, m, sintheta, costheta), 1.0e-8);
+            if m >= 1 {
+                assert_approx_eq!(plmcos_over_sintheta(l, m, sintheta, costheta), plmcos(l, m, sintheta, costheta) / sintheta, 1.0e-9);
+            }
+        }
+
+        // At the poles: P_l^1 / sin(theta) -> (+-1)^(l+1) l(l+1)/2, d^2P_l^0/dtheta^2 -> -(+-1)^l l(l+1)/2,
+        // dP_l^1/dtheta -> (+-1)^(l+1) l(l+1)/2, and all other limits vanish
+
+        for l in 1..6_u16 {
+            let half = f64::from(l * (l + 1)) / 2.0;
+            let sign = if l.is_odd() { 1.0 } else { -1.0 };
+            assert_approx_eq!(plmcos_over_sintheta(l, 1, 0.0, 1.0), half, 1.0e-12);
+            assert_approx_eq!(plmcos_over_sintheta(l, 1, 0.0, -1.0), sign * half, 1.0e-12);
+            assert_approx_eq!(deriv1_plmcos_dtheta_polesafe(l, 1, 0.0, 1.0), half, 1.0e-12);
+            assert_approx_eq!(deriv1_plmcos_dtheta_polesafe(l, 0, 0.0, 1.0), 0.0, 1.0e-12);
+            assert_approx_eq!(deriv2_plmcos_dtheta_polesafe(l, 0, 0.0, 1.0), -half, 1.0e-12);
+            assert_approx_eq!(deriv2_plmcos_dtheta_polesafe(l, 0, 0.0, -1.0), sign * half, 1.0e-12);
+            if l >= 2 {
+                let quarter = f64::from((l - 1) * l * (l + 1) * (l + 2)) / 8.0;
+                assert_approx_eq!(plmcos_over_sin2theta(l, 2, 0.0, 1.0), quarter, 1.0e-12);
+                assert_approx_eq!(plmcos_over_sin2theta(l, 2, 0.0, -1.0), -sign * quarter, 1.0e-12);
+                assert_approx_eq!(plmcos_over_sin2theta(l, 2, sintheta, costheta), plmcos(l, 2, sintheta, costheta) / (sintheta * sintheta), 1.0e-9);
+                assert_approx_eq!(plmcos_over_sintheta(l, 2, 0.0, 1.0), 0.0, 1.0e-12);
+                assert_approx_eq!(deriv1_plmcos_dtheta_polesafe(l, 2, 0.0, -1.0), 0.0, 1.0e-12);
+            }
+        }
+    }
 }
 
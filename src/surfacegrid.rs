@@ -0,0 +1,439 @@
+use std::f64::consts::PI;
+use std::collections::HashMap;
+use is_odd::IsOdd;
+
+
+// All grids tessellate the unit sphere in a frame with its own polar z-axis. The line profile
+// synthesizer puts the grid in the observer's frame, where the z'-axis points towards the observer,
+// so that the elements with normal[2] > 0 form the visible hemisphere. Areas are solid angles,
+// i.e. in units of R^2, and the areas of a grid add up to 4 pi.
+
+
+
+
+
+/// A surface element of a tessellation of the unit sphere
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SurfaceElement {
+    pub area: f64,                   // solid angle of the element [R^2]
+    pub normal: [f64; 3],            // outward unit normal
+    pub centroid: [f64; 3],          // centre of the element on the unit sphere [R]
+}
+
+
+
+
+
+impl SurfaceElement {
+
+    /// Creates an element of a spherical grid, where the normal coincides with the centroid
+    ///
+    /// # Arguments
+    ///
+    /// * `area`  - solid angle of the element [R^2]
+    /// * `theta` - colatitude of the centre of the element [rad]
+    /// * `phi`   - azimuth of the centre of the element [rad]
+    ///
+    pub fn on_sphere(area: f64, theta: f64, phi: f64) -> Self {
+        let centroid = [theta.sin() * phi.cos(), theta.sin() * phi.sin(), theta.cos()];
+        return SurfaceElement { area, normal: centroid, centroid };
+    }
+
+
+
+
+    /// Returns the colatitude [rad] of the centroid
+    ///
+    pub fn theta(&self) -> f64 {
+        let r = norm(self.centroid);
+        return (self.centroid[2] / r).clamp(-1.0, 1.0).acos();
+    }
+
+
+
+
+    /// Returns the azimuth [rad] of the centroid, in [0, 2 pi)
+    ///
+    pub fn phi(&self) -> f64 {
+        return self.centroid[1].atan2(self.centroid[0]).rem_euclid(2.0 * PI);
+    }
+}
+
+
+
+
+
+
+
+
+
+/// A tessellation of the stellar surface into elements. A grid is computed once and can be reused
+/// for all time steps of a run.
+///
+pub trait SurfaceGrid {
+
+    /// Returns all surface elements of the grid
+    ///
+    fn elements(&self) -> &[SurfaceElement];
+}
+
+
+
+
+
+
+
+
+
+/// A rectangular grid with `n_theta` equal steps in colatitude and `n_phi` equal steps in azimuth.
+/// The elements near the poles are much smaller than those near the equator.
+///
+#[derive(Clone, Debug)]
+pub struct RectangularGrid {
+    elements: Vec<SurfaceElement>,
+}
+
+
+
+
+
+impl RectangularGrid {
+
+    /// Creates a grid of `n_theta` x `n_phi` elements
+    ///
+    pub fn new(n_theta: usize, n_phi: usize) -> Self {
+
+        assert!(n_theta > 0 && n_phi > 0, "RectangularGrid::new(): empty grid");
+
+        let dphi = 2.0 * PI / n_phi as f64;
+        let mut elements = Vec::with_capacity(n_theta * n_phi);
+        for j in 0..n_theta {
+
+            // The centre in area of the band lies halfway between the bounding values of cos(theta)
+
+            let costheta_top = (PI * j as f64 / n_theta as f64).cos();
+            let costheta_bottom = (PI * (j + 1) as f64 / n_theta as f64).cos();
+            let theta = (0.5 * (costheta_top + costheta_bottom)).acos();
+            for k in 0..n_phi {
+                elements.push(SurfaceElement::on_sphere(dphi * (costheta_top - costheta_bottom), theta, dphi * (k as f64 + 0.5)));
+            }
+        }
+
+        return RectangularGrid { elements };
+    }
+}
+
+
+
+
+
+impl SurfaceGrid for RectangularGrid {
+    fn elements(&self) -> &[SurfaceElement] {
+        return &self.elements;
+    }
+}
+
+
+
+
+
+
+
+
+
+/// A grid of `n_bands` latitude bands of equal width in cos(theta), each divided into `n_phi`
+/// elements of equal width in azimuth, so that all elements have the same area. This is the grid
+/// of the original Pulstar code, which covered the visible hemisphere with bands in mu = cos(theta').
+///
+#[derive(Clone, Debug)]
+pub struct LatitudeBandGrid {
+    elements: Vec<SurfaceElement>,
+}
+
+
+
+
+
+impl LatitudeBandGrid {
+
+    /// Creates a grid of `n_bands` x `n_phi` elements of area 4 pi / (n_bands n_phi)
+    ///
+    pub fn new(n_bands: usize, n_phi: usize) -> Self {
+
+        assert!(n_bands > 0 && n_phi > 0, "LatitudeBandGrid::new(): empty grid");
+
+        let area = 4.0 * PI / (n_bands * n_phi) as f64;
+        let mut elements = Vec::with_capacity(n_bands * n_phi);
+        for j in 0..n_bands {
+            let theta = (1.0 - 2.0 * (j as f64 + 0.5) / n_bands as f64).acos();
+            for k in 0..n_phi {
+                elements.push(SurfaceElement::on_sphere(area, theta, 2.0 * PI * (k as f64 + 0.5) / n_phi as f64));
+            }
+        }
+
+        return LatitudeBandGrid { elements };
+    }
+}
+
+
+
+
+
+impl SurfaceGrid for LatitudeBandGrid {
+    fn elements(&self) -> &[SurfaceElement] {
+        return &self.elements;
+    }
+}
+
+
+
+
+
+
+
+
+
+/// A HEALPix-like grid (Gorski et al. 2005, ApJ 622, 759) of 12 n_side^2 elements of equal area,
+/// with the element centres on iso-latitude rings as in the RING scheme of HEALPix.
+///
+#[derive(Clone, Debug)]
+pub struct HealpixGrid {
+    elements: Vec<SurfaceElement>,
+}
+
+
+
+
+
+impl HealpixGrid {
+
+    /// Creates a grid with resolution parameter `n_side`, i.e. with 12 n_side^2 elements
+    ///
+    pub fn new(n_side: usize) -> Self {
+
+        assert!(n_side > 0, "HealpixGrid::new(): n_side = 0");
+
+        let n = n_side as f64;
+        let area = PI / (3.0 * n * n);
+        let mut elements = Vec::with_capacity(12 * n_side * n_side);
+
+        for ring in 1..4 * n_side {
+            if ring < n_side || ring > 3 * n_side {
+
+                // The polar caps: ring i has 4i elements at z = +-(1 - i^2 / (3 n_side^2))
+
+                let i = if ring < n_side { ring } else { 4 * n_side - ring };
+                let z = 1.0 - (i * i) as f64 / (3.0 * n * n);
+                let z = if ring < n_side { z } else { -z };
+                for j in 1..=4 * i {
+                    elements.push(SurfaceElement::on_sphere(area, z.acos(), PI / (2.0 * i as f64) * (j as f64 - 0.5)));
+                }
+            } else {
+
+                // The equatorial belt: 4 n_side elements per ring, shifted by half an element on alternate rings
+
+                let z = 4.0 / 3.0 - 2.0 * ring as f64 / (3.0 * n);
+                let shift = if (ring - n_side).is_odd() { 0.0 } else { 0.5 };
+                for j in 1..=4 * n_side {
+                    elements.push(SurfaceElement::on_sphere(area, z.acos(), PI / (2.0 * n) * (j as f64 - shift)));
+                }
+            }
+        }
+
+        return HealpixGrid { elements };
+    }
+}
+
+
+
+
+
+impl SurfaceGrid for HealpixGrid {
+    fn elements(&self) -> &[SurfaceElement] {
+        return &self.elements;
+    }
+}
+
+
+
+
+
+
+
+
+
+/// A triangulated mesh obtained by recursively subdividing the 20 faces of an icosahedron into
+/// 4 triangles and projecting the new vertices onto the unit sphere, giving 20 4^n nearly equal
+/// triangles. The area of an element is the solid angle of its spherical triangle, its centroid
+/// the projection onto the sphere of the mean of its vertices, and its normal the normal of the
+/// flat triangle.
+///
+#[derive(Clone, Debug)]
+pub struct IcosahedralGrid {
+    vertices: Vec<[f64; 3]>,
+    triangles: Vec<[usize; 3]>,
+    elements: Vec<SurfaceElement>,
+}
+
+
+
+
+
+impl IcosahedralGrid {
+
+    /// Creates a mesh with `subdivisions` levels of subdivision, i.e. with 20 4^subdivisions triangles
+    ///
+    pub fn new(subdivisions: u32) -> Self {
+
+        // The 12 vertices of the icosahedron are the cyclic permutations of (0, +-1, +-golden ratio)
+
+        let golden = 0.5 * (1.0 + 5.0_f64.sqrt());
+        let mut vertices: Vec<[f64; 3]> = [[-1.0, golden, 0.0], [1.0, golden, 0.0], [-1.0, -golden, 0.0], [1.0, -golden, 0.0],
+                                           [0.0, -1.0, golden], [0.0, 1.0, golden], [0.0, -1.0, -golden], [0.0, 1.0, -golden],
+                                           [golden, 0.0, -1.0], [golden, 0.0, 1.0], [-golden, 0.0, -1.0], [-golden, 0.0, 1.0]]
+                                           .iter().map(|v| normalise(*v)).collect();
+        let mut triangles: Vec<[usize; 3]> = vec![[0, 11, 5], [0, 5, 1], [0, 1, 7], [0, 7, 10], [0, 10, 11],
+                                                  [1, 5, 9], [5, 11, 4], [11, 10, 2], [10, 7, 6], [7, 1, 8],
+                                                  [3, 9, 4], [3, 4, 2], [3, 2, 6], [3, 6, 8], [3, 8, 9],
+                                                  [4, 9, 5], [2, 4, 11], [6, 2, 10], [8, 6, 7], [9, 8, 1]];
+
+        for _ in 0..subdivisions {
+            let mut midpoints: HashMap<(usize, usize), usize> = HashMap::new();
+            let mut midpoint = |a: usize, b: usize, vertices: &mut Vec<[f64; 3]>| -> usize {
+                *midpoints.entry((a.min(b), a.max(b))).or_insert_with(|| {
+                    vertices.push(normalise(add(vertices[a], vertices[b])));
+                    vertices.len() - 1
+                })
+            };
+            let mut subdivided = Vec::with_capacity(4 * triangles.len());
+            for [a, b, c] in triangles {
+                let ab = midpoint(a, b, &mut vertices);
+                let bc = midpoint(b, c, &mut vertices);
+                let ca = midpoint(c, a, &mut vertices);
+                subdivided.extend([[a, ab, ca], [b, bc, ab], [c, ca, bc], [ab, bc, ca]]);
+            }
+            triangles = subdivided;
+        }
+
+        // The solid angle of a spherical triangle follows from Van Oosterom & Strackee (1983):
+        //     tan(Omega/2) = |a . (b x c)| / (1 + a.b + b.c + c.a)
+
+        let elements = triangles.iter().map(|[a, b, c]| {
+            let (a, b, c) = (vertices[*a], vertices[*b], vertices[*c]);
+            let numerator = dot(a, cross(b, c)).abs();
+            let denominator = 1.0 + dot(a, b) + dot(b, c) + dot(c, a);
+            let area = 2.0 * numerator.atan2(denominator);
+            let centroid = normalise(add(add(a, b), c));
+            let mut normal = normalise(cross([b[0] - a[0], b[1] - a[1], b[2] - a[2]], [c[0] - a[0], c[1] - a[1], c[2] - a[2]]));
+            if dot(normal, centroid) < 0.0 {
+                normal = [-normal[0], -normal[1], -normal[2]];
+            }
+            SurfaceElement { area, normal, centroid }
+        }).collect();
+
+        return IcosahedralGrid { vertices, triangles, elements };
+    }
+
+
+
+
+    /// Returns the vertices of the mesh on the unit sphere
+    ///
+    pub fn vertices(&self) -> &[[f64; 3]] {
+        return &self.vertices;
+    }
+
+
+
+
+    /// Returns the triangles of the mesh as indices into vertices()
+    ///
+    pub fn triangles(&self) -> &[[usize; 3]] {
+        return &self.triangles;
+    }
+}
+
+
+
+
+
+impl SurfaceGrid for IcosahedralGrid {
+    fn elements(&self) -> &[SurfaceElement] {
+        return &self.elements;
+    }
+}
+
+
+
+
+
+
+
+
+
+/// Small helpers for 3D vectors
+///
+fn dot(a: [f64; 3], b: [f64; 3]) -> f64 {
+    return a[0] * b[0] + a[1] * b[1] + a[2] * b[2];
+}
+
+fn cross(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    return [a[1] * b[2] - a[2] * b[1], a[2] * b[0] - a[0] * b[2], a[0] * b[1] - a[1] * b[0]];
+}
+
+fn add(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    return [a[0] + b[0], a[1] + b[1], a[2] + b[2]];
+}
+
+fn norm(a: [f64; 3]) -> f64 {
+    return dot(a, a).sqrt();
+}
+
+fn normalise(a: [f64; 3]) -> [f64; 3] {
+    let r = norm(a);
+    return [a[0] / r, a[1] / r, a[2] / r];
+}
+
+
+
+
+
+
+
+
+
+
+
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn test_grids_cover_the_sphere() {
+
+        // The areas add up to 4 pi, the area-weighted normals cancel, and the visible
+        // hemisphere projects onto a disk of area pi.
+
+        let grids: Vec<Box<dyn SurfaceGrid>> = vec![Box::new(RectangularGrid::new(90, 180)), Box::new(LatitudeBandGrid::new(90, 180)),
+                                                    Box::new(HealpixGrid::new(40)), Box::new(IcosahedralGrid::new(5))];
+        for grid in grids.iter() {
+            let elements = grid.elements();
+            let total: f64 = elements.iter().map(|element| element.area).sum();
+            let projected: f64 = elements.iter().filter(|element| element.normal[2] > 0.0).map(|element| element.area * element.normal[2]).sum();
+            assert_approx_eq!(total, 4.0 * PI, 1.0e-10);
+            for axis in 0..3 {
+                assert_approx_eq!(elements.iter().map(|element| element.area * element.normal[axis]).sum::<f64>(), 0.0, 1.0e-10);
+            }
+            assert_approx_eq!(projected, PI, 2.0e-3);
+        }
+
+        assert_eq!(HealpixGrid::new(4).elements().len(), 192);
+        assert_eq!(IcosahedralGrid::new(2).elements().len(), 320);
+    }
+}
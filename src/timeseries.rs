@@ -0,0 +1,392 @@
+use std::f64::consts::PI;
+use crate::mode::*;
+use crate::lineprofile::*;
+use crate::limbdarkening::*;
+use crate::intrinsicprofile::AtmosphereScaling;
+use crate::photometry::*;
+
+
+// A TimeSeries describes an observing campaign: the mid-exposure times, the exposure time and the noise.
+// The model can be any function of time, e.g. a line profile or a light curve, which is averaged over the
+// exposure and to which noise is added. The noise is reproducible: the same seed gives the same series.
+
+
+
+
+
+/// The noise added to the synthetic observations
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Noise {
+    None,
+    Gaussian { sigma: f64 },         // Gaussian noise with standard deviation sigma, in the units of the model
+    Poisson { snr: f64 },            // Photon noise on a normalised flux, with S/N = snr at flux 1
+}
+
+
+
+
+impl Noise {
+
+    /// Gaussian noise with sigma = 1/snr, i.e. with signal-to-noise ratio `snr` in the continuum of a normalised flux
+    ///
+    pub fn gaussian_snr(snr: f64) -> Self {
+        assert!(snr > 0.0, "Noise::gaussian_snr(): snr <= 0");
+        return Noise::Gaussian { sigma: 1.0 / snr };
+    }
+}
+
+
+
+
+
+
+
+
+
+/// Synthetic observations at a list of epochs
+///
+pub struct TimeSeries {
+    epochs: Vec<f64>,                // Mid-exposure times [d]
+    exposure_time: f64,              // [d]
+    n_subexposures: usize,           // Number of evaluations of the model per exposure
+    noise: Noise,
+    seed: u64,
+}
+
+
+
+
+
+
+
+
+
+impl TimeSeries {
+
+    /// Creates a time series at the given epochs, with instantaneous exposures and without noise
+    ///
+    /// # Arguments
+    ///
+    /// * `epochs` - mid-exposure times, not necessarily evenly spaced [d]
+    ///
+    pub fn new(epochs: Vec<f64>) -> Self {
+        TimeSeries { epochs, exposure_time: 0.0, n_subexposures: 1, noise: Noise::None, seed: 0 }
+    }
+
+
+
+
+    /// Creates a time series with a constant cadence during a set of observing windows, e.g. nights,
+    /// so that there are gaps between the windows
+    ///
+    /// # Arguments
+    ///
+    /// * `cadence` - time between two consecutive exposures [d]
+    /// * `windows` - the (start, end) of each observing window [d]
+    ///
+    pub fn from_cadence(cadence: f64, windows: &[(f64, f64)]) -> Self {
+
+        assert!(cadence > 0.0, "TimeSeries::from_cadence(): cadence <= 0");
+
+        let mut epochs = Vec::new();
+        for (start, end) in windows.iter() {
+
+            // Allow for round-off, so that an exposure at the end of the window is not lost
+
+            let n_exposures = ((end - start) / cadence + 1.0e-9).floor() as usize + 1;
+            epochs.extend((0..n_exposures).map(|j| start + j as f64 * cadence));
+        }
+
+        return TimeSeries::new(epochs);
+    }
+
+
+
+
+    /// Integrates the model over an exposure time `exposure_time` [d], by averaging it over `n_subexposures`
+    /// equidistant times within each exposure
+    ///
+    pub fn with_exposure(mut self, exposure_time: f64, n_subexposures: usize) -> Self {
+        assert!(exposure_time >= 0.0 && n_subexposures > 0, "TimeSeries::with_exposure(): invalid exposure");
+        self.exposure_time = exposure_time;
+        self.n_subexposures = n_subexposures;
+        self
+    }
+
+
+
+
+    /// Adds noise, drawn from a random number generator initialised with `seed`
+    ///
+    pub fn with_noise(mut self, noise: Noise, seed: u64) -> Self {
+        self.noise = noise;
+        self.seed = seed;
+        self
+    }
+
+
+
+
+    /// Returns the mid-exposure times [d]
+    ///
+    pub fn epochs(&self) -> &[f64] {
+        return &self.epochs;
+    }
+
+
+
+
+    /// Evaluates a model, e.g. a spectrum, at each epoch, averaged over the exposure and with noise
+    ///
+    /// # Arguments
+    ///
+    /// * `model` - function returning the noise-free observation at a given time [d]
+    ///
+    pub fn evaluate<F: Fn(f64) -> Vec<f64>>(&self, model: F) -> Vec<Vec<f64>> {
+
+        let mut random = Random::new(self.seed);
+
+        return self.epochs.iter().map(|epoch| {
+
+            // The midpoint rule over the exposure
+
+            let mut observation = vec![];
+            for k in 0..self.n_subexposures {
+                let time = epoch + self.exposure_time * ((k as f64 + 0.5) / self.n_subexposures as f64 - 0.5);
+                let values = model(time);
+                if observation.is_empty() {
+                    observation = vec![0.0; values.len()];
+                }
+                for (sum, value) in observation.iter_mut().zip(values.iter()) {
+                    *sum += value / self.n_subexposures as f64;
+                }
+            }
+
+            observation.iter().map(|value| self.add_noise(*value, &mut random)).collect()
+        }).collect();
+    }
+
+
+
+
+    /// Evaluates a scalar model, e.g. a light curve or a radial velocity, at each epoch, averaged over the
+    /// exposure and with noise
+    ///
+    pub fn evaluate_scalar<F: Fn(f64) -> f64>(&self, model: F) -> Vec<f64> {
+        return self.evaluate(|time| vec![model(time)]).into_iter().map(|observation| observation[0]).collect();
+    }
+
+
+
+
+    /// Computes the continuum-normalised line profiles of a star pulsating in the modes `modes`
+    ///
+    /// # Arguments
+    ///
+    /// * `synthesizer` - the line profile synthesizer
+    /// * `modes`       - the pulsation modes
+    /// * `velocities`  - velocity grid of the profiles [km/s]
+    ///
+    pub fn line_profiles(&self, synthesizer: &LineProfileSynthesizer, modes: &ModeSet, velocities: &[f64]) -> Vec<Vec<f64>> {
+        return self.evaluate(|time| synthesizer.profile(modes, time, velocities));
+    }
+
+
+
+
+    /// Computes the normalised flux 1 + delta F / F of a star pulsating in the modes `modes`.
+    /// See flux_amplitude() for the arguments.
+    ///
+    pub fn light_curve(&self, modes: &ModeSet, inclination: f64, mass: f64, radius: f64,
+                       limb_darkening: &dyn LimbDarkening, scaling: &AtmosphereScaling) -> Vec<f64> {
+        return self.evaluate_scalar(|time| 1.0 + relative_flux_variation(modes, time, inclination, mass, radius, limb_darkening, scaling));
+    }
+
+
+
+
+    /// Computes the Lagrangian displacement (delta r, delta theta, delta phi) of the surface point (theta, phi) [rad],
+    /// i.e. displacement() at the phase of each mode at each epoch, averaged over the exposure and with noise.
+    /// See displacement() for the conventions.
+    ///
+    pub fn displacements(&self, modes: &ModeSet, theta: f64, phi: f64) -> Vec<(f64, f64, f64)> {
+        return self.evaluate(|time| {
+            let (delta_r, delta_theta, delta_phi) = modes.displacement(time, theta, phi);
+            vec![delta_r, delta_theta, delta_phi]
+        }).into_iter().map(|observation| (observation[0], observation[1], observation[2])).collect();
+    }
+
+
+
+
+    /// Adds a noise realisation to the noise-free value
+    ///
+    fn add_noise(&self, value: f64, random: &mut Random) -> f64 {
+        return match self.noise {
+            Noise::None => value,
+            Noise::Gaussian { sigma } => value + sigma * random.gaussian(),
+            Noise::Poisson { snr } => random.poisson(value.max(0.0) * snr * snr) / (snr * snr),
+        };
+    }
+}
+
+
+
+
+
+
+
+
+
+/// The SplitMix64 generator (Steele, Lea & Flood 2014), which passes BigCrush and needs only a 64-bit state
+///
+struct Random {
+    state: u64,
+}
+
+
+
+
+impl Random {
+
+    fn new(seed: u64) -> Self {
+        Random { state: seed }
+    }
+
+
+
+
+    /// Returns a uniform deviate in [0, 1) with 53 random bits
+    ///
+    fn uniform(&mut self) -> f64 {
+        self.state = self.state.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^= z >> 31;
+        return (z >> 11) as f64 / (1u64 << 53) as f64;
+    }
+
+
+
+
+    /// Returns a standard normal deviate with the Box-Muller transform
+    ///
+    fn gaussian(&mut self) -> f64 {
+        let u1 = 1.0 - self.uniform();
+        let u2 = self.uniform();
+        return (-2.0 * u1.ln()).sqrt() * (2.0 * PI * u2).cos();
+    }
+
+
+
+
+    /// Returns a Poisson deviate with mean `mean`, by multiplying uniform deviates for small means
+    /// (Knuth), and with the rounded normal approximation for means above 50
+    ///
+    fn poisson(&mut self, mean: f64) -> f64 {
+
+        if mean > 50.0 {
+            return (mean + mean.sqrt() * self.gaussian()).round().max(0.0);
+        }
+
+        let limit = (-mean).exp();
+        let mut count = 0;
+        let mut product = self.uniform();
+        while product > limit {
+            count += 1;
+            product *= self.uniform();
+        }
+
+        return f64::from(count);
+    }
+}
+
+
+
+
+
+
+
+
+
+
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intrinsicprofile::GaussianProfile;
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn test_epochs_and_exposure_smearing() {
+
+        // Two nights with a gap
+
+        let series = TimeSeries::from_cadence(0.01, &[(0.0, 0.3), (1.0, 1.3)]);
+        assert_eq!(series.epochs().len(), 62);
+        assert_approx_eq!(series.epochs()[31], 1.0, 1.0e-14);
+
+        // Averaging a sinusoid over the exposure reduces its amplitude by sin(pi f T) / (pi f T)
+
+        let (frequency, exposure_time) = (10.0, 0.03);
+        let series = series.with_exposure(exposure_time, 200);
+        let smeared = series.evaluate_scalar(|time| (2.0 * PI * frequency * time).cos());
+        let reduction = (PI * frequency * exposure_time).sin() / (PI * frequency * exposure_time);
+        for (epoch, value) in series.epochs().iter().zip(smeared.iter()) {
+            assert_approx_eq!(*value, reduction * (2.0 * PI * frequency * epoch).cos(), 1.0e-4);
+        }
+    }
+
+    #[test]
+    fn test_noise_is_reproducible() {
+        let epochs: Vec<f64> = (0..20000).map(|j| j as f64).collect();
+        let constant = |_time: f64| 0.8;
+
+        let series = TimeSeries::new(epochs.clone()).with_noise(Noise::gaussian_snr(50.0), 42);
+        let first = series.evaluate_scalar(constant);
+        assert_eq!(first, series.evaluate_scalar(constant));
+        assert_ne!(first, TimeSeries::new(epochs.clone()).with_noise(Noise::gaussian_snr(50.0), 43).evaluate_scalar(constant));
+
+        // The sample mean and standard deviation for Gaussian and for Poisson noise, with sigma = sqrt(0.8)/snr
+
+        for (noise, sigma) in [(Noise::gaussian_snr(50.0), 0.02), (Noise::Poisson { snr: 5.0 }, 0.8_f64.sqrt() / 5.0),
+                               (Noise::Poisson { snr: 50.0 }, 0.8_f64.sqrt() / 50.0)] {
+            let values = TimeSeries::new(epochs.clone()).with_noise(noise, 7).evaluate_scalar(constant);
+            let mean = values.iter().sum::<f64>() / values.len() as f64;
+            let deviation = (values.iter().map(|v| (v - mean) * (v - mean)).sum::<f64>() / values.len() as f64).sqrt();
+            assert_approx_eq!(mean, 0.8, 4.0 * sigma / (values.len() as f64).sqrt());
+            assert_approx_eq!(deviation / sigma, 1.0, 0.03);
+        }
+    }
+
+    #[test]
+    fn test_models_at_the_epochs() {
+
+        // Without exposure time and noise the wrappers return the models at the epochs themselves
+
+        let series = TimeSeries::from_cadence(0.02, &[(0.0, 0.2)]);
+        let modes = ModeSet::new().with_mode(Mode::new(2, 1, 6.3, 0.01, 0.05, 0.4)).with_mode(Mode::new(1, -1, 7.8, 0.005, 0.1, 2.0));
+        let (theta, phi) = (0.8, 2.1);
+        for (epoch, displacement) in series.epochs().iter().zip(series.displacements(&modes, theta, phi).iter()) {
+            assert_eq!(*displacement, modes.displacement(*epoch, theta, phi));
+        }
+
+        let (inclination, mass, radius, law, scaling) = (1.0, 8.0, 4.0, LinearLaw { u: 0.6 }, AtmosphereScaling::default());
+        let light_curve = series.light_curve(&modes, inclination, mass, radius, &law, &scaling);
+        for (epoch, flux) in series.epochs().iter().zip(light_curve.iter()) {
+            assert_eq!(*flux, 1.0 + relative_flux_variation(&modes, *epoch, inclination, mass, radius, &law, &scaling));
+        }
+
+        let synthesizer = LineProfileSynthesizer::new(inclination, radius, 15000.0, 4.0, GaussianProfile::new(5.0, 0.4), law)
+                          .with_grid(10, 20);
+        let velocities: Vec<f64> = (0..=20).map(|j| -40.0 + 4.0 * j as f64).collect();
+        for (epoch, profile) in series.epochs().iter().zip(series.line_profiles(&synthesizer, &modes, &velocities).iter()) {
+            assert_eq!(*profile, synthesizer.profile(&modes, *epoch, &velocities));
+        }
+    }
+}
@@ -0,0 +1,201 @@
+use num_complex::Complex;
+use is_odd::IsOdd;
+
+
+/// The Wigner rotation matrix d^{(l)}(beta) with all elements d^{(l)}_{km}(beta), -l <= k, m <= l,
+/// in the convention of dlkm(), together with the complex D^{(l)}_{km}(alpha, beta, gamma).
+///
+/// The matrix is computed with the recursion of Risbo (1996, J. Geodesy 70, 383), which builds
+/// d^{(j)} from d^{(j-1/2)} in steps of a half, each element receiving four contributions weighted
+/// with cos(beta/2) and sin(beta/2), one of which is subtracted. The recursion is a sequence of
+/// products with rotation matrices of degree 1/2, so that, unlike the explicit alternating sum in
+/// dlkm(), it does not suffer from catastrophic cancellation, and it remains
+/// accurate for degrees of several hundreds. Computing the matrix of degree l costs O(l^3),
+/// i.e. O(l) per element, and yields the matrices of all lower degrees for free (see up_to()).
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct WignerD {
+    l: u16,
+    beta: f64,                       // [rad]
+    matrix: Vec<f64>,                // d^{(l)}_{km} at index (k + l) (2l + 1) + (m + l)
+}
+
+
+
+
+
+impl WignerD {
+
+    /// Computes the matrix d^{(l)}(beta)
+    ///
+    /// # Arguments
+    ///
+    /// * `l`    - the degree l >= 0
+    /// * `beta` - the rotation angle [rad]
+    ///
+    pub fn new(l: u16, beta: f64) -> Self {
+        return Self::recursion(l, beta, false).pop().unwrap();
+    }
+
+
+
+
+    /// Computes the matrices d^{(l)}(beta) for all degrees 0 <= l <= lmax
+    ///
+    /// # Arguments
+    ///
+    /// * `lmax` - the maximum degree
+    /// * `beta` - the rotation angle [rad]
+    ///
+    pub fn up_to(lmax: u16, beta: f64) -> Vec<Self> {
+        return Self::recursion(lmax, beta, true);
+    }
+
+
+
+
+    /// Runs Risbo's recursion up to degree lmax, keeping either all integer degrees or only lmax
+    ///
+    fn recursion(lmax: u16, beta: f64, keep_all: bool) -> Vec<Self> {
+
+        let p = (0.5 * beta).cos();
+        let q = (0.5 * beta).sin();
+
+        // Risbo's recursion works on the matrices of order n = 2j (integer or half-integer j),
+        // with indices 0 <= i, k <= n. Every second step is an integer degree.
+
+        let mut matrices = if keep_all || lmax == 0 { vec![WignerD { l: 0, beta, matrix: vec![1.0] }] } else { vec![] };
+        let mut previous: Vec<f64> = vec![1.0];
+        for n in 1..=2 * usize::from(lmax) {
+            let mut current = vec![0.0; (n + 1) * (n + 1)];
+            let nf = n as f64;
+            for i in 0..n {
+                for k in 0..n {
+                    let value = previous[i * n + k] / nf;
+                    if value == 0.0 {
+                        continue;
+                    }
+                    let (fi, fk) = (i as f64, k as f64);
+                    current[i * (n+1) + k]         += p * ((nf - fi) * (nf - fk)).sqrt() * value;
+                    current[(i+1) * (n+1) + k]     -= q * ((fi + 1.0) * (nf - fk)).sqrt() * value;
+                    current[i * (n+1) + k + 1]     += q * ((nf - fi) * (fk + 1.0)).sqrt() * value;
+                    current[(i+1) * (n+1) + k + 1] += p * ((fi + 1.0) * (fk + 1.0)).sqrt() * value;
+                }
+            }
+            if !n.is_odd() && (keep_all || n == 2 * usize::from(lmax)) {
+                matrices.push(WignerD { l: (n / 2) as u16, beta, matrix: current.clone() });
+            }
+            previous = current;
+        }
+
+        return matrices;
+    }
+
+
+
+
+    /// Returns the degree l of the matrix
+    ///
+    pub fn l(&self) -> u16 {
+        return self.l;
+    }
+
+
+
+
+    /// Returns the rotation angle beta [rad]
+    ///
+    pub fn beta(&self) -> f64 {
+        return self.beta;
+    }
+
+
+
+
+    /// Returns the element d^{(l)}_{km}(beta), which equals dlkm(l, k, m, beta)
+    ///
+    /// # Arguments
+    ///
+    /// * `k` - -l <= k <= l
+    /// * `m` - -l <= m <= l
+    ///
+    pub fn dkm(&self, k: i16, m: i16) -> f64 {
+        let l = self.l as i16;
+        assert!(k.abs() <= l && m.abs() <= l, "WignerD::dkm(): |k| > l or |m| > l");
+        let size = (2 * l + 1) as usize;
+        return self.matrix[(k + l) as usize * size + (m + l) as usize];
+    }
+
+
+
+
+    /// Returns the element D^{(l)}_{km}(alpha, beta, gamma) = e^{-i k alpha} d^{(l)}_{km}(beta) e^{-i m gamma}
+    /// of the rotation with Euler angles (alpha, beta, gamma) in the z-y-z convention
+    ///
+    /// # Arguments
+    ///
+    /// * `k`     - -l <= k <= l
+    /// * `m`     - -l <= m <= l
+    /// * `alpha` - first Euler angle, around the z-axis [rad]
+    /// * `gamma` - third Euler angle, around the new z-axis [rad]
+    ///
+    pub fn big_dkm(&self, k: i16, m: i16, alpha: f64, gamma: f64) -> Complex<f64> {
+        return Complex::from_polar(self.dkm(k, m), -f64::from(k) * alpha - f64::from(m) * gamma);
+    }
+}
+
+
+
+
+
+
+
+
+
+
+
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sphericalharmonics::*;
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn test_wignerd_equals_dlkm() {
+
+        // The explicit sum of dlkm() already loses a few digits to cancellation at l = 12
+
+        for beta in [0.0, 0.3, 1.2, 2.9] {
+            for wigner in WignerD::up_to(12, beta) {
+                let l = wigner.l() as i16;
+                for k in -l..=l {
+                    for m in -l..=l {
+                        assert_approx_eq!(wigner.dkm(k, m), dlkm(l as u32, k.into(), m.into(), beta), 1.0e-9);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_wignerd_is_orthogonal_for_large_l() {
+
+        // The rows of d^{(l)} are orthonormal, and |d^{(l)}_{0m}(beta)| = sqrt(4 pi/(2l+1)) |Pbar_l^m(cos(beta))|
+
+        let (l, beta) = (150_u16, 1.1_f64);
+        let wigner = WignerD::new(l, beta);
+        let size = l as i16;
+        for (k1, k2) in [(0, 0), (5, 5), (-150, -150), (7, 8), (-120, 140)] {
+            let product: f64 = (-size..=size).map(|m| wigner.dkm(k1, m) * wigner.dkm(k2, m)).sum();
+            assert_approx_eq!(product, if k1 == k2 { 1.0 } else { 0.0 }, 1.0e-10);
+        }
+        for m in [-150_i16, -17, 0, 3, 149] {
+            let expected = (4.0 * std::f64::consts::PI / f64::from(2*l + 1)).sqrt() * plmbar(l, m.unsigned_abs(), beta.sin(), beta.cos());
+            assert_approx_eq!(wigner.dkm(0, m).abs(), expected.abs(), 1.0e-10);
+        }
+    }
+}